@@ -0,0 +1,215 @@
+//! Continuous acquisition paced by the `DRDY` pin
+
+use crate::delay::{Delay, NoDelay};
+use crate::digital::{InputPin, NoPin};
+use crate::driver::{Driver, SampleSink};
+use crate::interface::SampleGrab;
+use crate::register::{Clock, DrdyReadyState, Mode};
+use crate::spi::Transfer;
+use crate::Error;
+
+/// Streaming acquisition of sample grabs, paced by the `DRDY` pin
+///
+/// Each sample grab is read with a null command once `DRDY` signals a new conversion.
+///
+/// # Overruns
+///
+/// The status word in each frame is the response to the previous null command,
+/// so it reports the channels which had data ready as soon as the previous grab was read.
+/// If any enabled channel already had new data by then, the acquisition is falling behind
+/// and conversions are being lost. Each such frame is counted as an overrun.
+pub struct Acquisition<
+    S: Transfer<W>,
+    W: Copy,
+    P: InputPin,
+    const CHANNELS: usize,
+    R = NoPin,
+    D = NoDelay,
+> {
+    driver: Driver<S, W, CHANNELS, R, D>,
+    drdy: P,
+    ready_state: DrdyReadyState,
+    enabled_channels: u8,
+    primed: bool,
+    overruns: u32,
+}
+
+impl<S, W, P, const CHANNELS: usize, R, D> Acquisition<S, W, P, CHANNELS, R, D>
+where
+    S: Transfer<W>,
+    W: Copy,
+    P: InputPin,
+    D: Delay,
+{
+    /// Start an acquisition using the `MODE` and `CLOCK` registers read from the device
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed
+    pub fn new(
+        mut driver: Driver<S, W, CHANNELS, R, D>,
+        drdy: P,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<Self, Error> {
+        let mode: Mode = driver.read_register(sink)?;
+        let clock: Clock = driver.read_register(sink)?;
+
+        Ok(Self::with_config(driver, drdy, &mode, &clock))
+    }
+
+    /// Start an acquisition using known `MODE` and `CLOCK` register values
+    ///
+    /// The registers are not read from the device, so they must match the device configuration
+    pub const fn with_config(
+        driver: Driver<S, W, CHANNELS, R, D>,
+        drdy: P,
+        mode: &Mode,
+        clock: &Clock,
+    ) -> Self {
+        Self {
+            driver,
+            drdy,
+            ready_state: mode.drdy_ready_state,
+            enabled_channels: clock.enabled_channels(),
+            primed: false,
+            overruns: 0,
+        }
+    }
+
+    /// Wait for the next conversion and read its sample grab
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if reading the `DRDY` pin or communication with the device failed,
+    /// or the device did not return a sample grab
+    pub fn next_sample(&mut self) -> Result<SampleGrab<CHANNELS>, Error> {
+        self.wait_for_drdy()?;
+
+        let resp = self.driver.read_frame()?;
+        if let Some(status) = resp.status {
+            if self.primed && status.is_overrun(self.enabled_channels) {
+                self.overruns = self.overruns.saturating_add(1);
+            }
+        }
+        self.primed = true;
+
+        resp.sample_grab.ok_or(Error::UnexpectedResponse)
+    }
+
+    /// The number of overruns detected since the acquisition started or was last cleared
+    pub const fn overruns(&self) -> u32 {
+        self.overruns
+    }
+
+    /// Reset the overrun count to zero
+    pub const fn clear_overruns(&mut self) {
+        self.overruns = 0;
+    }
+
+    /// Stop the acquisition and return the driver and `DRDY` pin
+    pub fn release(self) -> (Driver<S, W, CHANNELS, R, D>, P) {
+        (self.driver, self.drdy)
+    }
+
+    /// Wait until `DRDY` signals a new conversion
+    ///
+    /// In the logic low ready state, `DRDY` stays low until the data is read.
+    /// In the low pulse ready state, this waits for the end of the pulse so it is not seen twice.
+    fn wait_for_drdy(&mut self) -> Result<(), Error> {
+        while !self.drdy.is_low()? {}
+
+        if self.ready_state == DrdyReadyState::LowPulse {
+            while self.drdy.is_low()? {}
+        }
+
+        Ok(())
+    }
+}
+
+impl<S, W, P, const CHANNELS: usize, R, D> Iterator for Acquisition<S, W, P, CHANNELS, R, D>
+where
+    S: Transfer<W>,
+    W: Copy,
+    P: InputPin,
+    D: Delay,
+{
+    type Item = Result<SampleGrab<CHANNELS>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        Some(self.next_sample())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::digital::replay::ReplayPin;
+    use crate::driver::Discard;
+    use crate::register::Global;
+    use crate::spi::replay::{frame, open};
+
+    #[test]
+    fn acquire() {
+        let mode = Mode {
+            drdy_ready_state: DrdyReadyState::LowPulse,
+            ..Mode::default()
+        };
+        let clock = Clock {
+            channel1_en: false,
+            ..Clock::default()
+        };
+        let driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[mode.to_be_bytes()]),
+            frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[clock.to_be_bytes()]),
+            // Data left over from configuring the device is not an overrun
+            frame(&[[0x05, 0x0F], [0x00, 0x01], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x05, 0x00], [0x00, 0x02], [0, 0], [0, 0], [0, 0]]),
+            // Channel 1 is disabled
+            frame(&[[0x05, 0x02], [0x00, 0x03], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x05, 0x01], [0x00, 0x04], [0, 0], [0, 0], [0, 0]]),
+        ]);
+        let drdy = ReplayPin::new(&[
+            true, false, true, // Pulse
+            false, true, // Pulse
+            true, true, false, false, true, // Pulse
+            false, true, // Pulse
+        ]);
+
+        let mut acq = Acquisition::new(driver, drdy, &mut Discard).unwrap();
+        let samples: Vec<i32> = acq
+            .by_ref()
+            .take(4)
+            .map(|grab| grab.unwrap().into_i32_array()[0])
+            .collect();
+        assert_eq!(samples, [0x100, 0x200, 0x300, 0x400]);
+        assert_eq!(acq.overruns(), 1);
+
+        acq.clear_overruns();
+        assert_eq!(acq.overruns(), 0);
+
+        let (driver, drdy) = acq.release();
+        assert!(drdy.is_done());
+        assert!(driver.into_inner().release().is_done());
+    }
+
+    #[test]
+    fn logic_low_ready_state() {
+        let driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x05, 0x00], [0x00, 0x01], [0, 0], [0, 0], [0, 0]]),
+        ]);
+        let drdy = ReplayPin::new(&[true, true, false]);
+
+        let mut acq = Acquisition::with_config(driver, drdy, &Mode::default(), &Clock::default());
+        let _ = acq.driver.read_frame().unwrap();
+        let grab = acq.next_sample().unwrap();
+        assert_eq!(grab.into_i32_array()[0], 0x100);
+        assert!(acq.drdy.is_done());
+    }
+}
@@ -0,0 +1,375 @@
+//! Async message interface for use with [`embedded_hal_async`]
+//!
+//! This shares the frame encoding and decoding of [`interface::Ads131m`](crate::interface::Ads131m),
+//! but performs SPI transactions with an async [`SpiDevice`], and waits for new sample grabs
+//! on the `DRDY` pin instead of polling
+
+use embedded_hal_async::digital::Wait;
+use embedded_hal_async::spi::{Operation, SpiDevice};
+
+use crate::interface::{
+    Command, FrameCodec, LinkStats, Response, SampleGrab, MAX_READ_LEN, MAX_WRITE_LEN,
+};
+use crate::register::Mode;
+use crate::spi::prepare_in_place;
+use crate::Error;
+
+/// Async low level device message interface
+///
+/// This is the async counterpart of [`interface::Ads131m`](crate::interface::Ads131m)
+pub struct Ads131m<D: SpiDevice<u8>, const CHANNELS: usize> {
+    device: D,
+    read_buf: [u8; MAX_READ_LEN],
+    write_buf: [u8; MAX_WRITE_LEN],
+    codec: FrameCodec<CHANNELS>,
+}
+
+impl<D, const CHANNELS: usize> Ads131m<D, CHANNELS>
+where
+    D: SpiDevice<u8>,
+{
+    /// Communicate with the device
+    ///
+    /// # Note
+    /// Responses to commands are given on the following successful exchange,
+    /// ***not*** in the exchange in which they were sent
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed
+    pub async fn communicate(&mut self, command: Command) -> Result<Response<CHANNELS>, Error> {
+        let len = self.codec.prepare_frame(&command, &mut self.write_buf)?;
+
+        let send = &self.write_buf[..len.send];
+        let receive = &mut self.read_buf[..len.receive];
+        let overlap = prepare_in_place(send, receive);
+        let result = if overlap < send.len() {
+            self.device
+                .transaction(&mut [
+                    Operation::TransferInPlace(receive),
+                    Operation::Write(&send[overlap..]),
+                ])
+                .await
+        } else {
+            self.device
+                .transaction(&mut [Operation::TransferInPlace(receive)])
+                .await
+        };
+        result.map_err(|_| Error::SpiIOError)?;
+
+        self.codec.finish_frame(&self.read_buf[..len.receive])
+    }
+
+    /// Wait for the `DRDY` pin to signal a new sample grab, then read it from the device
+    ///
+    /// `DRDY` is active low, so this waits for a falling edge. This works for
+    /// both the logic low and the pulse `DRDY` formats
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if waiting on the pin or communication with the device failed,
+    /// or the device did not return a sample grab
+    pub async fn wait_for_sample_grab<P: Wait>(
+        &mut self,
+        drdy: &mut P,
+    ) -> Result<SampleGrab<CHANNELS>, Error> {
+        drdy.wait_for_falling_edge()
+            .await
+            .map_err(|_| Error::PinIOError)?;
+
+        self.communicate(Command::new_null())
+            .await?
+            .sample_grab
+            .ok_or(Error::UnexpectedResponse)
+    }
+
+    /// The communication health counters since the interface was opened or the counters were cleared
+    #[must_use]
+    pub const fn link_stats(&self) -> LinkStats {
+        self.codec.link_stats()
+    }
+
+    /// Reset every communication health counter to zero
+    pub fn clear_link_stats(&mut self) {
+        self.codec.clear_link_stats();
+    }
+
+    /// Destroy the driver instance and return the SPI device
+    pub fn release(self) -> D {
+        self.device
+    }
+
+    const fn new(device: D, mode: Mode) -> Self {
+        Self {
+            device,
+            read_buf: [0; MAX_READ_LEN],
+            write_buf: [0; MAX_WRITE_LEN],
+            codec: FrameCodec::new(mode),
+        }
+    }
+}
+
+impl<D: SpiDevice<u8>> Ads131m<D, 2> {
+    /// Initialize an `ADS131M02` ADC driver
+    ///
+    /// The SPI device must be configured for SPI mode 1 and the device must
+    /// have it's `MODE` register in the default (reset) state in order for
+    /// communications to work properly
+    pub fn open_ads131m02(device: D) -> Self {
+        Self::new(device, Mode::default())
+    }
+
+    /// Initialize an `ADS131M02` ADC driver with a custom initial state
+    ///
+    /// This does not configure the `MODE` register, instead this allows
+    /// communication with a device already in a non-default communication configuration
+    pub const fn open_ads131m02_with_mode(device: D, mode: Mode) -> Self {
+        Self::new(device, mode)
+    }
+}
+
+impl<D: SpiDevice<u8>> Ads131m<D, 3> {
+    /// Initialize an `ADS131M03` ADC driver
+    ///
+    /// The SPI device must be configured for SPI mode 1 and the device must
+    /// have it's `MODE` register in the default (reset) state in order for
+    /// communications to work properly
+    pub fn open_ads131m03(device: D) -> Self {
+        Self::new(device, Mode::default())
+    }
+
+    /// Initialize an `ADS131M03` ADC driver with a custom initial state
+    ///
+    /// This does not configure the `MODE` register, instead this allows
+    /// communication with a device already in a non-default communication configuration
+    pub const fn open_ads131m03_with_mode(device: D, mode: Mode) -> Self {
+        Self::new(device, mode)
+    }
+}
+
+impl<D: SpiDevice<u8>> Ads131m<D, 4> {
+    /// Initialize an `ADS131M04` ADC driver
+    ///
+    /// The SPI device must be configured for SPI mode 1 and the device must
+    /// have it's `MODE` register in the default (reset) state in order for
+    /// communications to work properly
+    pub fn open_ads131m04(device: D) -> Self {
+        Self::new(device, Mode::default())
+    }
+
+    /// Initialize an `ADS131M04` ADC driver with a custom initial state
+    ///
+    /// This does not configure the `MODE` register, instead this allows
+    /// communication with a device already in a non-default communication configuration
+    pub const fn open_ads131m04_with_mode(device: D, mode: Mode) -> Self {
+        Self::new(device, mode)
+    }
+}
+
+impl<D: SpiDevice<u8>> Ads131m<D, 6> {
+    /// Initialize an `ADS131M06` ADC driver
+    ///
+    /// The SPI device must be configured for SPI mode 1 and the device must
+    /// have it's `MODE` register in the default (reset) state in order for
+    /// communications to work properly
+    pub fn open_ads131m06(device: D) -> Self {
+        Self::new(device, Mode::default())
+    }
+
+    /// Initialize an `ADS131M06` ADC driver with a custom initial state
+    ///
+    /// This does not configure the `MODE` register, instead this allows
+    /// communication with a device already in a non-default communication configuration
+    pub const fn open_ads131m06_with_mode(device: D, mode: Mode) -> Self {
+        Self::new(device, mode)
+    }
+}
+
+impl<D: SpiDevice<u8>> Ads131m<D, 8> {
+    /// Initialize an `ADS131M08` ADC driver
+    ///
+    /// The SPI device must be configured for SPI mode 1 and the device must
+    /// have it's `MODE` register in the default (reset) state in order for
+    /// communications to work properly
+    pub fn open_ads131m08(device: D) -> Self {
+        Self::new(device, Mode::default())
+    }
+
+    /// Initialize an `ADS131M08` ADC driver with a custom initial state
+    ///
+    /// This does not configure the `MODE` register, instead this allows
+    /// communication with a device already in a non-default communication configuration
+    pub const fn open_ads131m08_with_mode(device: D, mode: Mode) -> Self {
+        Self::new(device, mode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use core::convert::Infallible;
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+    use std::sync::Arc;
+    use std::task::Wake;
+    use std::vec::Vec;
+
+    use embedded_hal_1::digital::ErrorKind;
+    use embedded_hal_async::digital::Wait;
+    use embedded_hal_async::spi::{ErrorType, Operation, SpiDevice};
+
+    use super::Ads131m;
+    use crate::interface::Command;
+    use crate::register::Address;
+    use crate::spi::replay::ReplaySpi;
+    use crate::spi::Transfer;
+    use crate::Error;
+
+    /// Waker which does nothing, as `Waker::noop` needs a newer compiler than the MSRV
+    struct NoopWaker;
+
+    impl Wake for NoopWaker {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// Poll a future that never needs to be woken to completion
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let waker = Waker::from(Arc::new(NoopWaker));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    /// Async wrapper around a replay interface, recording the whole frame sent in each transaction
+    struct AsyncReplay(ReplaySpi);
+
+    impl ErrorType for AsyncReplay {
+        type Error = Infallible;
+    }
+
+    impl SpiDevice<u8> for AsyncReplay {
+        async fn transaction(
+            &mut self,
+            operations: &mut [Operation<'_, u8>],
+        ) -> Result<(), Infallible> {
+            let mut sent = Vec::new();
+            let mut receive = None;
+            for op in operations {
+                match op {
+                    Operation::TransferInPlace(buf) => {
+                        sent.extend_from_slice(buf);
+                        receive = Some(buf);
+                    }
+                    Operation::Write(buf) => sent.extend_from_slice(buf),
+                    _ => unreachable!(),
+                }
+            }
+
+            let receive = receive.expect("no receive buffer");
+            self.0.transfer(&sent, receive).unwrap();
+            Ok(())
+        }
+    }
+
+    struct CountingPin(usize);
+
+    impl embedded_hal_1::digital::ErrorType for CountingPin {
+        type Error = Infallible;
+    }
+
+    impl Wait for CountingPin {
+        async fn wait_for_high(&mut self) -> Result<(), Infallible> {
+            unreachable!()
+        }
+
+        async fn wait_for_low(&mut self) -> Result<(), Infallible> {
+            unreachable!()
+        }
+
+        async fn wait_for_rising_edge(&mut self) -> Result<(), Infallible> {
+            unreachable!()
+        }
+
+        async fn wait_for_falling_edge(&mut self) -> Result<(), Infallible> {
+            self.0 += 1;
+            Ok(())
+        }
+
+        async fn wait_for_any_edge(&mut self) -> Result<(), Infallible> {
+            unreachable!()
+        }
+    }
+
+    struct FailingPin;
+
+    impl embedded_hal_1::digital::ErrorType for FailingPin {
+        type Error = ErrorKind;
+    }
+
+    impl Wait for FailingPin {
+        async fn wait_for_high(&mut self) -> Result<(), ErrorKind> {
+            Err(ErrorKind::Other)
+        }
+
+        async fn wait_for_low(&mut self) -> Result<(), ErrorKind> {
+            Err(ErrorKind::Other)
+        }
+
+        async fn wait_for_rising_edge(&mut self) -> Result<(), ErrorKind> {
+            Err(ErrorKind::Other)
+        }
+
+        async fn wait_for_falling_edge(&mut self) -> Result<(), ErrorKind> {
+            Err(ErrorKind::Other)
+        }
+
+        async fn wait_for_any_edge(&mut self) -> Result<(), ErrorKind> {
+            Err(ErrorKind::Other)
+        }
+    }
+
+    #[test]
+    fn drdy_error() {
+        let replay = ReplaySpi::new(3, &[]);
+        let mut adc = Ads131m::open_ads131m04(AsyncReplay(replay));
+
+        let result = block_on(adc.wait_for_sample_grab(&mut FailingPin));
+        assert_eq!(result.err(), Some(Error::PinIOError));
+        assert!(adc.release().0.sent.is_empty());
+    }
+
+    #[test]
+    fn communicate() {
+        let reset = [0xFF, 0x24, 0, 0x05, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let sample = [0x05, 0x00, 0, 0x12, 0x34, 0x56, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let register = [0x03, 0x00, 0];
+        let replay = ReplaySpi::new(3, &[&reset, &sample, &sample, &register]);
+        let mut adc = Ads131m::open_ads131m04(AsyncReplay(replay));
+        let mut drdy = CountingPin(0);
+
+        block_on(async {
+            let _ = adc.communicate(Command::new_null()).await.unwrap();
+            let grab = adc.wait_for_sample_grab(&mut drdy).await.unwrap();
+            assert_eq!(grab.into_i32_array()[0], 0x12_3456);
+
+            let _ = adc
+                .communicate(Command::new_read_register(Address::Clock))
+                .await
+                .unwrap();
+            let resp = adc.communicate(Command::new_null()).await.unwrap();
+            assert!(resp.sample_grab.is_none());
+            assert_eq!(resp.register_read.unwrap().data, [0x03, 0x00]);
+        });
+
+        let replay = adc.release().0;
+        assert_eq!(drdy.0, 1);
+        assert!(replay.is_done());
+        assert_eq!(replay.sent[2][..3], [0xA1, 0x80, 0x00]);
+    }
+}
@@ -0,0 +1,834 @@
+//! Calibration routines for the channel calibration registers
+
+use core::num::NonZeroUsize;
+
+use crate::delay::Delay;
+use crate::driver::{Driver, SampleSink};
+use crate::int::{i10, i24, u24};
+use crate::register::{
+    Address, Channel, ChannelConfig, ChannelGainCal, ChannelMux, ChannelOffsetCal, ChannelSpecific,
+    Clock, Global,
+};
+use crate::spi::Transfer;
+use crate::{timing, Error};
+
+/// The result of an offset calibration for a single channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OffsetCalibration {
+    /// The offset calibration written to the device
+    pub offset_cal: ChannelOffsetCal,
+
+    /// The average offset measured with the new calibration applied, in codes
+    pub residual: i32,
+}
+
+/// The known input used as a reference for gain calibration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GainReference {
+    /// A known input applied to the channel's selected input
+    ///
+    /// The zero point is measured with the channel inputs shorted
+    AppliedInput {
+        /// The expected value of the applied input, in codes
+        expected: i32,
+    },
+
+    /// The internal test signal, measured at both the positive and negative inputs
+    TestSignal {
+        /// The expected difference between the positive and negative test signal, in codes
+        expected_span: i32,
+    },
+}
+
+/// The result of a gain calibration for a single channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GainCalibration {
+    /// The gain calibration written to the device
+    pub gain_cal: ChannelGainCal,
+
+    /// The correction factor computed from the measurement, before clamping it to the range of [`ChannelGainCal`]
+    pub correction: f64,
+}
+
+/// The result of a phase calibration for a single channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PhaseCalibration {
+    /// The measured lag of the channel behind the reference channel, in samples
+    pub lag: f64,
+
+    /// The phase delay written to the channel configuration
+    pub phase: i10,
+}
+
+impl<S, W, const CHANNELS: usize, P, D> Driver<S, W, CHANNELS, P, D>
+where
+    S: Transfer<W>,
+    W: Copy,
+    D: Delay,
+{
+    /// Calibrate the offset of each channel in `channels`
+    ///
+    /// The inputs of each channel are shorted while `samples` sample grabs are averaged,
+    /// then the measured mean is written to the offset calibration registers,
+    /// which the device subtracts from each conversion.
+    /// The residual offset is measured over another `samples` sample grabs,
+    /// before the original input of each channel is restored.
+    /// Channels listed more than once are only calibrated once.
+    ///
+    /// After each change the driver waits for the decimation filter to settle, then waits one
+    /// conversion period before reading each sample grab, timed from the `CLOCK` register and the
+    /// [`clkin_hz`](Self::clkin_hz) frequency. This needs a delay provider from
+    /// [`with_delay`](Self::with_delay), otherwise stale and unsettled conversions are averaged
+    ///
+    /// Returns the result for each calibrated channel, indexed by channel
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or a channel is not supported by the device.
+    /// The original channel inputs are still restored if possible when this fails
+    pub fn calibrate_offset(
+        &mut self,
+        channels: &[Channel],
+        samples: NonZeroUsize,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<[Option<OffsetCalibration>; CHANNELS], Error> {
+        for &channel in channels {
+            channel_index::<CHANNELS>(channel)?;
+        }
+
+        let timing = self.conversion_timing(sink)?;
+        let mut configs = [None; CHANNELS];
+        for &channel in channels {
+            let idx = usize::from(u8::from(channel));
+            if configs[idx].is_none() {
+                let config: ChannelConfig = self.read_channel_register(channel, sink)?;
+                configs[idx] = Some((channel, config));
+            }
+        }
+
+        let result = self.measure_offsets(&configs, timing, samples, sink);
+        let restored = self.restore_channel_configs(&configs, sink);
+        let results = result?;
+        restored?;
+
+        Ok(results)
+    }
+
+    /// Measure and correct the offset of each channel in `configs`, leaving its inputs shorted
+    fn measure_offsets(
+        &mut self,
+        configs: &[Option<(Channel, ChannelConfig)>; CHANNELS],
+        timing: ConversionTiming,
+        samples: NonZeroUsize,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<[Option<OffsetCalibration>; CHANNELS], Error> {
+        for &(channel, config) in configs.iter().flatten() {
+            let shorted = ChannelConfig {
+                mux: ChannelMux::Shorted,
+                ..config
+            };
+            self.write_channel_register(shorted, channel, sink)?;
+            self.write_offset_cal(ChannelOffsetCal::default(), channel, sink)?;
+        }
+
+        let offsets = self.average_settled(timing, samples)?;
+
+        let mut results = [None; CHANNELS];
+        for &(channel, _) in configs.iter().flatten() {
+            let idx = usize::from(u8::from(channel));
+            let offset_cal = ChannelOffsetCal {
+                offset: i24::new_clamped(offsets[idx]),
+            };
+            self.write_offset_cal(offset_cal, channel, sink)?;
+
+            results[idx] = Some(OffsetCalibration {
+                offset_cal,
+                residual: 0,
+            });
+        }
+
+        let residuals = self.average_settled(timing, samples)?;
+        for (idx, result) in results.iter_mut().enumerate() {
+            if let Some(result) = result {
+                result.residual = residuals[idx];
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Write back the saved configuration of each channel in `configs`
+    ///
+    /// Every channel is attempted even if an earlier write fails, returning the first error
+    fn restore_channel_configs(
+        &mut self,
+        configs: &[Option<(Channel, ChannelConfig)>; CHANNELS],
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error> {
+        let mut result = Ok(());
+        for &(channel, config) in configs.iter().flatten() {
+            let restored = self.write_channel_register(config, channel, sink);
+            result = result.and(restored);
+        }
+
+        result
+    }
+
+    /// Calibrate the gain of `channel` against a known reference
+    ///
+    /// The gain calibration is reset to `1.0`, then the span between the two points of the reference
+    /// is measured, averaging `samples` sample grabs at each point. The correction factor is the
+    /// expected span divided by the measured span, which is written to the gain calibration registers.
+    /// The original input of the channel is restored afterwards.
+    ///
+    /// The offset calibration is not changed, and does not affect the measured span.
+    /// Sample grabs are paced in the same way as [`calibrate_offset`](Self::calibrate_offset)
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// the channel is not supported by the device,
+    /// or the measured span is zero or has the opposite sign to the expected span.
+    /// The original channel input is still restored if possible when this fails,
+    /// but the gain calibration may have been reset to `1.0`
+    pub fn calibrate_gain(
+        &mut self,
+        channel: Channel,
+        reference: GainReference,
+        samples: NonZeroUsize,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<GainCalibration, Error> {
+        channel_index::<CHANNELS>(channel)?;
+        let timing = self.conversion_timing(sink)?;
+        let config: ChannelConfig = self.read_channel_register(channel, sink)?;
+
+        let result = self.measure_span(channel, config, reference, timing, samples, sink);
+        let restored = self.write_channel_register(config, channel, sink);
+        let (expected, measured) = result?;
+        restored?;
+
+        if measured == 0 || expected.signum() != measured.signum() {
+            return Err(Error::InsufficientSignal);
+        }
+
+        let correction = f64::from(expected) / f64::from(measured);
+        let gain_cal = gain_cal_for_correction(correction);
+        self.write_gain_cal(gain_cal, channel, sink)?;
+
+        Ok(GainCalibration {
+            gain_cal,
+            correction,
+        })
+    }
+
+    /// Calibrate the phase of `channel` so it is aligned with a reference channel
+    ///
+    /// `reference` and `signal` are the samples of the reference channel and `channel`,
+    /// taken from the same sample grabs while the same sine wave is applied to both.
+    /// The lag is estimated with [`estimate_lag`], converted to modulator clock cycles using
+    /// the oversampling ratio read from the device, and subtracted from the channel's phase delay.
+    ///
+    /// The phase delay can only shift a channel by -512 to 511 modulator clock cycles,
+    /// which is less than one sample at most oversampling ratios, so larger lags are clamped
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// the channel is not supported by the device,
+    /// or either signal has no rising zero crossings
+    pub fn calibrate_phase(
+        &mut self,
+        channel: Channel,
+        reference: &[i32],
+        signal: &[i32],
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<PhaseCalibration, Error> {
+        channel_index::<CHANNELS>(channel)?;
+        let lag = estimate_lag(reference, signal).ok_or(Error::InsufficientSignal)?;
+
+        let clock: Clock = self.read_register(sink)?;
+        let config: ChannelConfig = self.read_channel_register(channel, sink)?;
+
+        let cycles = lag * f64::from(clock.modulator_cycles_per_conversion());
+        let phase = i10::new_clamped(config.phase.get().saturating_sub(round_to_i16(cycles)));
+        self.write_channel_register(ChannelConfig { phase, ..config }, channel, sink)?;
+
+        Ok(PhaseCalibration { lag, phase })
+    }
+
+    /// Reset the gain calibration of `channel`, then measure the span of `reference`
+    ///
+    /// Returns the expected and measured spans, leaving the channel on the last input measured
+    fn measure_span(
+        &mut self,
+        channel: Channel,
+        config: ChannelConfig,
+        reference: GainReference,
+        timing: ConversionTiming,
+        samples: NonZeroUsize,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(i32, i32), Error> {
+        let idx = usize::from(u8::from(channel));
+        self.write_gain_cal(ChannelGainCal::default(), channel, sink)?;
+
+        let (expected, measured) = match reference {
+            GainReference::AppliedInput { expected } => {
+                let zero = self.measure_input(
+                    channel,
+                    config,
+                    ChannelMux::Shorted,
+                    timing,
+                    samples,
+                    sink,
+                )?;
+                let applied =
+                    self.measure_input(channel, config, config.mux, timing, samples, sink)?;
+                (expected, applied[idx] - zero[idx])
+            }
+            GainReference::TestSignal { expected_span } => {
+                let positive = self.measure_input(
+                    channel,
+                    config,
+                    ChannelMux::PositiveTest,
+                    timing,
+                    samples,
+                    sink,
+                )?;
+                let negative = self.measure_input(
+                    channel,
+                    config,
+                    ChannelMux::NegativeTest,
+                    timing,
+                    samples,
+                    sink,
+                )?;
+                (expected_span, positive[idx] - negative[idx])
+            }
+        };
+
+        Ok((expected, measured))
+    }
+
+    /// Switch `channel` to `mux`, and average each channel once the filter has settled
+    fn measure_input(
+        &mut self,
+        channel: Channel,
+        config: ChannelConfig,
+        mux: ChannelMux,
+        timing: ConversionTiming,
+        samples: NonZeroUsize,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<[i32; CHANNELS], Error> {
+        self.write_channel_register(ChannelConfig { mux, ..config }, channel, sink)?;
+        self.average_settled(timing, samples)
+    }
+
+    /// The conversion timing with the current `CLOCK` settings
+    fn conversion_timing(
+        &mut self,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<ConversionTiming, Error> {
+        let clock = Clock::from_be_bytes(self.shadowed_raw(Address::Clock, sink)?);
+        Ok(ConversionTiming::new(self.clkin_hz(), &clock))
+    }
+
+    /// Write both halves of a channel's gain calibration in one command
+    fn write_gain_cal(
+        &mut self,
+        gain_cal: ChannelGainCal,
+        channel: Channel,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error> {
+        let (msb, lsb) = gain_cal.into_parts();
+        self.write_registers(
+            Address::ChannelGainCalMsb(channel),
+            &[msb.to_be_bytes(), lsb.to_be_bytes()],
+            sink,
+        )
+    }
+
+    /// Write both halves of a channel's offset calibration in one command
+    fn write_offset_cal(
+        &mut self,
+        offset_cal: ChannelOffsetCal,
+        channel: Channel,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error> {
+        let (msb, lsb) = offset_cal.into_parts();
+        self.write_registers(
+            Address::ChannelOffsetCalMsb(channel),
+            &[msb.to_be_bytes(), lsb.to_be_bytes()],
+            sink,
+        )
+    }
+
+    /// Wait for the filter to settle after a change, then average the value of each channel
+    /// over `samples` new sample grabs, rounding to the nearest code
+    fn average_settled(
+        &mut self,
+        timing: ConversionTiming,
+        samples: NonZeroUsize,
+    ) -> Result<[i32; CHANNELS], Error> {
+        self.delay_us(timing.settling_us);
+
+        let mut sums = [0_i64; CHANNELS];
+        for _ in 0..samples.get() {
+            self.delay_us(timing.period_us);
+            let values = self.read_sample_grab()?.into_i32_array();
+            for (sum, value) in sums.iter_mut().zip(values) {
+                *sum += i64::from(value);
+            }
+        }
+
+        let count = i64::try_from(samples.get()).unwrap_or(i64::MAX);
+        Ok(sums.map(|sum| {
+            // The mean of 24-bit values always fits in an i32
+            i32::try_from((2 * sum + count).div_euclid(2 * count)).unwrap_or_default()
+        }))
+    }
+}
+
+/// The waits for new conversions, in microseconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ConversionTiming {
+    /// The time for the decimation filter to settle after a change
+    settling_us: u32,
+    /// The time between conversions
+    period_us: u32,
+}
+
+impl ConversionTiming {
+    fn new(clkin_hz: u32, clock: &Clock) -> Self {
+        Self {
+            settling_us: ceil_us(timing::settling_time(clkin_hz, clock)),
+            period_us: ceil_us(timing::data_rate(clkin_hz, clock).recip()),
+        }
+    }
+}
+
+/// Convert a time in seconds to microseconds, rounding up and saturating at the limits of `u32`
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn ceil_us(seconds: f64) -> u32 {
+    let us = seconds * 1e6;
+    // Float to integer casts truncate towards zero and saturate
+    let whole = us as u32;
+    if f64::from(whole) < us {
+        whole.saturating_add(1)
+    } else {
+        whole
+    }
+}
+
+/// Estimate how many samples `signal` lags behind `reference`
+///
+/// Both inputs are samples of the same sine wave taken at the same times. The DC level is removed,
+/// then the rising zero crossings of each signal are found with linear interpolation between samples.
+/// Each crossing of `reference` is paired with the nearest crossing of `signal`,
+/// and the lag is the average time between them.
+///
+/// The lag must be less than half a period of the sine wave for the crossings to be paired correctly.
+/// A negative lag means `signal` leads `reference`
+///
+/// Returns `None` if either signal has no rising zero crossings
+#[must_use]
+pub fn estimate_lag(reference: &[i32], signal: &[i32]) -> Option<f64> {
+    let mut total = 0.0;
+    let mut count = 0_u32;
+    for reference_time in rising_crossings(reference) {
+        let nearest = rising_crossings(signal)
+            .map(|signal_time| signal_time - reference_time)
+            .min_by(|a, b| abs(*a).total_cmp(&abs(*b)))?;
+
+        total += nearest;
+        count += 1;
+    }
+
+    if count == 0 {
+        None
+    } else {
+        Some(total / f64::from(count))
+    }
+}
+
+/// The times of each rising zero crossing of `samples` with the DC level removed, in samples
+fn rising_crossings(samples: &[i32]) -> impl Iterator<Item = f64> + '_ {
+    let sum: i64 = samples.iter().copied().map(i64::from).sum();
+    #[allow(clippy::cast_precision_loss)]
+    let mean = sum as f64 / samples.len().max(1) as f64;
+
+    samples
+        .windows(2)
+        .enumerate()
+        .filter_map(move |(idx, pair)| {
+            let before = f64::from(pair[0]) - mean;
+            let after = f64::from(pair[1]) - mean;
+            #[allow(clippy::cast_precision_loss)]
+            (before < 0.0 && after >= 0.0).then(|| idx as f64 + before / (before - after))
+        })
+}
+
+const fn abs(value: f64) -> f64 {
+    if value < 0.0 {
+        -value
+    } else {
+        value
+    }
+}
+
+/// Round to the nearest integer, saturating at the limits of `i16`
+#[allow(clippy::cast_possible_truncation)]
+fn round_to_i16(value: f64) -> i16 {
+    // Float to integer casts truncate towards zero and saturate
+    if value < 0.0 {
+        (value - 0.5) as i16
+    } else {
+        (value + 0.5) as i16
+    }
+}
+
+/// Convert a gain correction factor to the nearest [`ChannelGainCal`], clamping it to the representable range
+///
+/// Negative, infinite, and `NaN` corrections are clamped as well
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn gain_cal_for_correction(correction: f64) -> ChannelGainCal {
+    // Float to integer casts saturate, and NaN becomes zero
+    let word = (correction * f64::from(1_u32 << 23) + 0.5) as u32;
+
+    ChannelGainCal {
+        gain: u24::new_clamped(word),
+    }
+}
+
+/// Get the index of `channel`, checking that it is supported by the device
+fn channel_index<const CHANNELS: usize>(channel: Channel) -> Result<usize, Error> {
+    let idx = usize::from(u8::from(channel));
+    if idx >= CHANNELS {
+        return Err(Error::UnsupportedChannel);
+    }
+
+    Ok(idx)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use float_cmp::assert_approx_eq;
+
+    use super::*;
+    use crate::driver::Discard;
+    use crate::spi::replay::{frame, open};
+
+    /// Build a 24-bit status frame with a sample for channel 0
+    fn grab(sample: i32) -> Vec<u8> {
+        let mut frame = frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]);
+        frame[3..6].copy_from_slice(&i24::new_masked(sample).to_be_bytes());
+        frame
+    }
+
+    /// Records the length of each delay
+    #[derive(Debug, Default)]
+    struct Delays(Vec<u32>);
+
+    impl Delay for Delays {
+        fn delay_us(&mut self, us: u32) {
+            self.0.push(us);
+        }
+    }
+
+    #[test]
+    fn offset() {
+        let config = ChannelConfig {
+            dc_block_disable: true,
+            ..ChannelConfig::default()
+        };
+        let status = grab(0);
+        let mut frames = Vec::new();
+        frames.extend([
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[Clock::default().to_be_bytes()]),
+            status.clone(),
+            frame(&[config.to_be_bytes()]),
+            status.clone(),
+            frame(&[[0x44, 0x80], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            status.clone(),
+            frame(&[[0x45, 0x01], [0, 0], [0, 0], [0, 0], [0, 0]]),
+        ]);
+        frames.extend([grab(255), grab(257)]);
+        frames.extend([
+            status.clone(),
+            frame(&[[0x45, 0x01], [0, 0], [0, 0], [0, 0], [0, 0]]),
+        ]);
+        frames.extend([grab(1), grab(2)]);
+        frames.extend([
+            status,
+            frame(&[[0x44, 0x80], [0, 0], [0, 0], [0, 0], [0, 0]]),
+        ]);
+        let mut driver = open(&frames).with_delay(Delays::default());
+
+        let results = driver
+            .calibrate_offset(
+                &[Channel::Zero],
+                NonZeroUsize::new(2).unwrap(),
+                &mut Discard,
+            )
+            .unwrap();
+        assert_eq!(
+            results,
+            [
+                Some(OffsetCalibration {
+                    offset_cal: ChannelOffsetCal {
+                        offset: i24::new_masked(256)
+                    },
+                    residual: 2,
+                }),
+                None,
+                None,
+                None
+            ]
+        );
+
+        let (intf, _, delays) = driver.release();
+        let intf = intf.release();
+        assert!(intf.is_done());
+        // Shorted inputs
+        assert_eq!(intf.sent[4][..6], [0x64, 0x80, 0x00, 0x00, 0x05, 0x00]);
+        // Measured mean
+        assert_eq!(
+            intf.sent[10][..9],
+            [0x65, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00]
+        );
+        // Restored inputs
+        assert_eq!(intf.sent[14][..6], [0x64, 0x80, 0x00, 0x00, 0x04, 0x00]);
+        // Settling time and conversion period at 4 kSPS
+        assert_eq!(delays.0, [750, 250, 250, 750, 250, 250]);
+    }
+
+    #[test]
+    fn gain_test_signal() {
+        let status = grab(0);
+        let mut frames = Vec::new();
+        frames.extend([
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[Clock::default().to_be_bytes()]),
+            status.clone(),
+            frame(&[ChannelConfig::default().to_be_bytes()]),
+            status.clone(),
+            frame(&[[0x46, 0x01], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            status.clone(),
+            frame(&[[0x44, 0x80], [0, 0], [0, 0], [0, 0], [0, 0]]),
+        ]);
+        frames.extend([grab(999), grab(1001)]);
+        frames.extend([
+            status.clone(),
+            frame(&[[0x44, 0x80], [0, 0], [0, 0], [0, 0], [0, 0]]),
+        ]);
+        frames.extend([grab(-1000), grab(-1000)]);
+        frames.extend([
+            status.clone(),
+            frame(&[[0x44, 0x80], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            status,
+            frame(&[[0x46, 0x01], [0, 0], [0, 0], [0, 0], [0, 0]]),
+        ]);
+        let mut driver = open(&frames);
+
+        let result = driver
+            .calibrate_gain(
+                Channel::Zero,
+                GainReference::TestSignal {
+                    expected_span: 2500,
+                },
+                NonZeroUsize::new(2).unwrap(),
+                &mut Discard,
+            )
+            .unwrap();
+        assert_approx_eq!(f64, result.correction, 1.25);
+        assert_eq!(result.gain_cal.gain.get(), 0xA0_0000);
+
+        let intf = driver.into_inner().release();
+        assert!(intf.is_done());
+        // Gain reset to 1.0
+        assert_eq!(
+            intf.sent[4][..9],
+            [0x66, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+        // Positive and negative test signals
+        assert_eq!(intf.sent[6][..6], [0x64, 0x80, 0x00, 0x00, 0x02, 0x00]);
+        assert_eq!(intf.sent[10][..6], [0x64, 0x80, 0x00, 0x00, 0x03, 0x00]);
+        // Restored input
+        assert_eq!(intf.sent[14][..6], [0x64, 0x80, 0x00, 0x00, 0x00, 0x00]);
+        // Corrected gain
+        assert_eq!(
+            intf.sent[16][..9],
+            [0x66, 0x01, 0x00, 0xA0, 0x00, 0x00, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn gain_clamped() {
+        assert_eq!(gain_cal_for_correction(1.0), ChannelGainCal::default());
+        assert_eq!(gain_cal_for_correction(0.5).gain.get(), 0x40_0000);
+        assert_eq!(gain_cal_for_correction(2.0).gain.get(), u24::MAX);
+        assert_eq!(gain_cal_for_correction(-1.0).gain.get(), u24::MIN);
+        assert_eq!(gain_cal_for_correction(f64::INFINITY).gain.get(), u24::MAX);
+        assert_eq!(gain_cal_for_correction(f64::NAN).gain.get(), u24::MIN);
+    }
+
+    /// Sample a sine wave with a period of 40 samples, delayed by `lag` samples
+    fn sine(lag: f64) -> Vec<i32> {
+        (0..400)
+            .map(|n| {
+                let t = (f64::from(n) - lag) / 40.0;
+                #[allow(clippy::cast_possible_truncation)]
+                let sample = (1_000_000.0 * (2.0 * std::f64::consts::PI * t).sin()) as i32;
+                sample + 1000
+            })
+            .collect()
+    }
+
+    #[test]
+    fn lag_estimate() {
+        let reference = sine(0.0);
+        for lag in [-5.0, -0.25, 0.0, 0.1, 0.5, 3.75] {
+            let estimate = estimate_lag(&reference, &sine(lag)).unwrap();
+            assert!((estimate - lag).abs() < 1e-3, "{estimate} != {lag}");
+        }
+
+        assert_eq!(estimate_lag(&reference, &[1000; 400]), None);
+        assert_eq!(estimate_lag(&[], &reference), None);
+    }
+
+    #[test]
+    fn phase() {
+        let config = ChannelConfig {
+            phase: i10::try_new(16).unwrap(),
+            ..ChannelConfig::default()
+        };
+        let status = grab(0);
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[Clock::default().to_be_bytes()]),
+            status.clone(),
+            frame(&[config.to_be_bytes()]),
+            status,
+            frame(&[[0x47, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+        ]);
+
+        let result = driver
+            .calibrate_phase(Channel::One, &sine(0.0), &sine(0.25), &mut Discard)
+            .unwrap();
+        assert_approx_eq!(f64, result.lag, 0.25, epsilon = 1e-3);
+        // 0.25 samples at an oversampling ratio of 1024
+        assert_eq!(result.phase.get(), 16 - 256);
+
+        let intf = driver.into_inner().release();
+        assert!(intf.is_done());
+        assert_eq!(intf.sent[4][..6], [0x67, 0x00, 0x00, 0xC4, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn phase_insufficient_signal() {
+        let mut driver = open(&[]);
+        assert_eq!(
+            driver.calibrate_phase(Channel::One, &[0; 16], &[0; 16], &mut Discard),
+            Err(Error::InsufficientSignal)
+        );
+    }
+
+    #[test]
+    fn offset_unsupported_channel() {
+        let mut driver = open(&[]);
+        assert_eq!(
+            driver.calibrate_offset(&[Channel::Four], NonZeroUsize::MIN, &mut Discard),
+            Err(Error::UnsupportedChannel)
+        );
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn offset_sim() {
+        use crate::interface::Ads131m;
+        use crate::sim::Simulator;
+
+        let sim = Simulator::<4>::new().with_input_offsets([300, -1200, 0, 0]);
+        let mut driver = Driver::new(Ads131m::open_ads131m04(sim)).with_delay(Delays::default());
+
+        // Channel 0 is listed twice, and must still be restored to its analog input
+        let results = driver
+            .calibrate_offset(
+                &[Channel::Zero, Channel::One, Channel::Zero],
+                NonZeroUsize::new(4).unwrap(),
+                &mut Discard,
+            )
+            .unwrap();
+        for (result, offset) in results.iter().zip([300, -1200]) {
+            assert_eq!(
+                *result,
+                Some(OffsetCalibration {
+                    offset_cal: ChannelOffsetCal {
+                        offset: i24::new_masked(offset)
+                    },
+                    residual: 0,
+                })
+            );
+        }
+        assert_eq!(results[2..], [None, None]);
+
+        let (intf, _, delays) = driver.release();
+        let sim = intf.release();
+        for channel in &sim.registers().channels {
+            assert_eq!(channel.config.mux, ChannelMux::AnalogIn);
+        }
+        assert_eq!(delays.0, [750, 250, 250, 250, 250, 750, 250, 250, 250, 250]);
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn offset_restored_on_error() {
+        use crate::fault::{Fault, FaultInjector};
+        use crate::interface::Ads131m;
+        use crate::sim::Simulator;
+
+        let mut intf = FaultInjector::new(Simulator::<4>::new());
+        // The first sample grab, after shorting the inputs
+        intf.schedule(8, Fault::SpiError).unwrap();
+        let mut driver = Driver::new(Ads131m::open_ads131m04(intf));
+
+        assert_eq!(
+            driver.calibrate_offset(&[Channel::Zero], NonZeroUsize::MIN, &mut Discard),
+            Err(Error::SpiIOError)
+        );
+
+        let sim = driver.into_inner().release().release();
+        assert_eq!(sim.registers().channels[0].config.mux, ChannelMux::AnalogIn);
+    }
+
+    #[cfg(feature = "sim")]
+    #[test]
+    fn gain_insufficient_signal() {
+        use crate::interface::Ads131m;
+        use crate::sim::Simulator;
+
+        // No applied input, and an applied input of the wrong polarity
+        for input in [0, -1000] {
+            let sim = Simulator::<4, _>::with_waveform([input; 4]);
+            let mut driver = Driver::new(Ads131m::open_ads131m04(sim));
+
+            assert_eq!(
+                driver.calibrate_gain(
+                    Channel::Zero,
+                    GainReference::AppliedInput { expected: 1000 },
+                    NonZeroUsize::MIN,
+                    &mut Discard,
+                ),
+                Err(Error::InsufficientSignal)
+            );
+
+            let sim = driver.into_inner().release();
+            assert_eq!(sim.registers().channels[0].config.mux, ChannelMux::AnalogIn);
+        }
+    }
+}
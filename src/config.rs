@@ -0,0 +1,522 @@
+//! Typed configuration of the device registers
+
+use crate::delay::Delay;
+use crate::driver::{Driver, SampleSink};
+use crate::interface::Command;
+use crate::register::{
+    Address, Channel, ChannelConfig, ChannelSpecific, Clock, Config, DcBlock, DrdyNotReadyState,
+    DrdyReadyState, DrdySource, Gain1, Gain2, Global, GlobalChopDelay, Mode, OversamplingRatio,
+    PgaGain, PowerMode, Threshold, ThresholdLsb, ThresholdMsb,
+};
+use crate::register_map::RegisterMap;
+use crate::spi::Transfer;
+use crate::timing::{closest_oversampling, MAX_TURBO_CHANNELS, NOMINAL_CLKIN_HZ};
+use crate::Error;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A builder for a validated device configuration
+///
+/// Settings for channels the device does not have are rejected by [`build`](Self::build),
+/// rather than silently ignored. The configuration starts from the reset value of each register,
+/// with the `RESET` bit of the `MODE` register cleared
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdcConfig<const CHANNELS: usize> {
+    clkin_hz: u32,
+    sample_rate_hz: Option<f64>,
+    mode: Mode,
+    clock: Clock,
+    gains: [PgaGain; 8],
+    config: Config,
+    dc_block: DcBlock,
+    channels: [ChannelConfig; 8],
+    configured: [bool; 8],
+}
+
+impl<const CHANNELS: usize> AdcConfig<CHANNELS> {
+    /// Start a configuration for a device running from the nominal `CLKIN` frequency
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_clkin(NOMINAL_CLKIN_HZ)
+    }
+
+    /// Start a configuration for a device running from a `CLKIN` frequency of `clkin_hz`
+    #[must_use]
+    pub fn with_clkin(clkin_hz: u32) -> Self {
+        Self {
+            clkin_hz,
+            sample_rate_hz: None,
+            mode: Mode {
+                reset: false,
+                ..Mode::default()
+            },
+            clock: Clock {
+                channel0_en: CHANNELS > 0,
+                channel1_en: CHANNELS > 1,
+                channel2_en: CHANNELS > 2,
+                channel3_en: CHANNELS > 3,
+                channel4_en: CHANNELS > 4,
+                channel5_en: CHANNELS > 5,
+                channel6_en: CHANNELS > 6,
+                channel7_en: CHANNELS > 7,
+                ..Clock::default()
+            },
+            gains: [PgaGain::default(); 8],
+            config: Config::default(),
+            dc_block: DcBlock::default(),
+            channels: [ChannelConfig::default(); 8],
+            configured: [false; 8],
+        }
+    }
+
+    /// Use `mode` as the base `MODE` register, including its communication settings
+    ///
+    /// DRDY settings made after this replace the ones in `mode`
+    #[must_use]
+    pub const fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Select the oversampling ratio giving the data rate closest to `sample_rate_hz`
+    ///
+    /// Turbo mode is used if it is the closest, and the device supports it
+    #[must_use]
+    pub const fn sample_rate(mut self, sample_rate_hz: f64) -> Self {
+        self.sample_rate_hz = Some(sample_rate_hz);
+        self
+    }
+
+    /// Set the oversampling ratio directly, instead of selecting it from a sample rate
+    #[must_use]
+    pub const fn oversampling_ratio(mut self, oversampling_ratio: OversamplingRatio) -> Self {
+        self.sample_rate_hz = None;
+        self.clock.oversampling_ratio = oversampling_ratio;
+        self
+    }
+
+    /// Enable turbo mode, which is only valid with [`OversamplingRatio::Osr128`]
+    #[must_use]
+    pub const fn turbo_mode(mut self, turbo_mode: bool) -> Self {
+        self.clock.turbo_mode = turbo_mode;
+        self
+    }
+
+    /// Set the power mode
+    #[must_use]
+    pub const fn power_mode(mut self, power_mode: PowerMode) -> Self {
+        self.clock.power_mode = power_mode;
+        self
+    }
+
+    /// Enable or disable the conversions of `channel`
+    #[must_use]
+    pub fn channel_enabled(mut self, channel: Channel, enabled: bool) -> Self {
+        match channel {
+            Channel::Zero => self.clock.channel0_en = enabled,
+            Channel::One => self.clock.channel1_en = enabled,
+            Channel::Two => self.clock.channel2_en = enabled,
+            Channel::Three => self.clock.channel3_en = enabled,
+            Channel::Four => self.clock.channel4_en = enabled,
+            Channel::Five => self.clock.channel5_en = enabled,
+            Channel::Six => self.clock.channel6_en = enabled,
+            Channel::Seven => self.clock.channel7_en = enabled,
+        }
+        self.configured[usize::from(u8::from(channel))] = true;
+        self
+    }
+
+    /// Set the PGA gain of `channel`
+    #[must_use]
+    pub fn gain(mut self, channel: Channel, gain: PgaGain) -> Self {
+        let idx = usize::from(u8::from(channel));
+        self.gains[idx] = gain;
+        self.configured[idx] = true;
+        self
+    }
+
+    /// Set the `CHx_CFG` register of `channel`
+    #[must_use]
+    pub fn channel_config(mut self, channel: Channel, config: ChannelConfig) -> Self {
+        let idx = usize::from(u8::from(channel));
+        self.channels[idx] = config;
+        self.configured[idx] = true;
+        self
+    }
+
+    /// Enable global-chop mode with `delay`, or disable it with `None`
+    #[must_use]
+    pub const fn global_chop(mut self, delay: Option<GlobalChopDelay>) -> Self {
+        if let Some(delay) = delay {
+            self.config.global_chop_enable = true;
+            self.config.global_chop_delay = delay;
+        } else {
+            self.config.global_chop_enable = false;
+        }
+        self
+    }
+
+    /// Set the DC block filter shared by every channel
+    #[must_use]
+    pub const fn dc_block(mut self, dc_block: DcBlock) -> Self {
+        self.dc_block = dc_block;
+        self
+    }
+
+    /// Set the `DRDY` pin behaviour
+    #[must_use]
+    pub const fn drdy(
+        mut self,
+        source: DrdySource,
+        not_ready_state: DrdyNotReadyState,
+        ready_state: DrdyReadyState,
+    ) -> Self {
+        self.mode.drdy_source = source;
+        self.mode.drdy_not_ready_state = not_ready_state;
+        self.mode.drdy_ready_state = ready_state;
+        self
+    }
+
+    /// Validate the configuration and build the register set
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Error::UnsupportedChannel`] if a channel the device does not have was configured.
+    ///
+    /// Will return [`Error::InvalidConfig`] if turbo mode is enabled on a device which does not support it
+    /// or without [`OversamplingRatio::Osr128`], if `CLKIN` is faster than the power mode allows,
+    /// or if the target sample rate is not positive
+    pub fn build(&self) -> Result<AdcRegisters<CHANNELS>, Error> {
+        if self.configured[CHANNELS.min(8)..].contains(&true) {
+            return Err(Error::UnsupportedChannel);
+        }
+
+        let mut clock = self.clock;
+        if let Some(sample_rate_hz) = self.sample_rate_hz {
+            (clock.oversampling_ratio, clock.turbo_mode) =
+                closest_oversampling(self.clkin_hz, sample_rate_hz, CHANNELS)
+                    .ok_or(Error::InvalidConfig)?;
+        }
+
+        if clock.turbo_mode
+            && (CHANNELS > MAX_TURBO_CHANNELS
+                || clock.oversampling_ratio != OversamplingRatio::Osr128)
+        {
+            return Err(Error::InvalidConfig);
+        }
+
+        if self.clkin_hz > max_clkin_hz(clock.power_mode) {
+            return Err(Error::InvalidConfig);
+        }
+
+        Ok(AdcRegisters {
+            mode: self.mode,
+            clock,
+            gain1: Gain1 {
+                pga_gain0: self.gains[0],
+                pga_gain1: self.gains[1],
+                pga_gain2: self.gains[2],
+                pga_gain3: self.gains[3],
+            },
+            gain2: Gain2 {
+                pga_gain4: self.gains[4],
+                pga_gain5: self.gains[5],
+                pga_gain6: self.gains[6],
+                pga_gain7: self.gains[7],
+            },
+            config: self.config,
+            threshold: Threshold {
+                dc_block: self.dc_block,
+                ..Threshold::default()
+            },
+            channels: core::array::from_fn(|channel| self.channels[channel]),
+        })
+    }
+}
+
+impl<const CHANNELS: usize> Default for AdcConfig<CHANNELS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The highest `CLKIN` frequency supported in each power mode, in hertz
+const fn max_clkin_hz(power_mode: PowerMode) -> u32 {
+    match power_mode {
+        PowerMode::HighResolution => NOMINAL_CLKIN_HZ,
+        PowerMode::LowPower => NOMINAL_CLKIN_HZ / 2,
+        PowerMode::VeryLowPower => NOMINAL_CLKIN_HZ / 4,
+    }
+}
+
+/// A validated set of configuration registers, built by [`AdcConfig`]
+///
+/// The channel calibration registers are not included, so applying a configuration
+/// does not disturb an existing calibration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AdcRegisters<const CHANNELS: usize> {
+    /// `MODE` register
+    pub mode: Mode,
+
+    /// `CLOCK` register
+    pub clock: Clock,
+
+    /// `GAIN1` register
+    pub gain1: Gain1,
+
+    /// `GAIN2` register, which is not written on devices with 4 or fewer channels
+    pub gain2: Gain2,
+
+    /// `CFG` register
+    pub config: Config,
+
+    /// `THRSHLD_MSB` and `THRSHLD_LSB` registers
+    pub threshold: Threshold,
+
+    /// `CHx_CFG` register of each channel
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_array"))]
+    pub channels: [ChannelConfig; CHANNELS],
+}
+
+impl<const CHANNELS: usize> AdcRegisters<CHANNELS> {
+    /// The commands which write this configuration to the device, in order
+    ///
+    /// `MODE` is written first, so the rest of the commands use its communication settings.
+    /// `CLOCK` is written last, so conversions start once every other register is configured
+    pub fn commands(&self) -> impl Iterator<Item = Command> + '_ {
+        self.writes()
+            .filter_map(|(address, word)| Command::new_write_registers(address, &[word]).ok())
+    }
+
+    /// Write this configuration to the device, in the order of [`commands`](Self::commands)
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not acknowledge a write
+    pub fn apply<S, W, P, D>(
+        &self,
+        driver: &mut Driver<S, W, CHANNELS, P, D>,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error>
+    where
+        S: Transfer<W>,
+        W: Copy,
+        D: Delay,
+    {
+        for (address, word) in self.writes() {
+            driver.write_registers(address, &[word], sink)?;
+        }
+
+        Ok(())
+    }
+
+    /// Each register write, in order
+    #[allow(clippy::similar_names)]
+    fn writes(&self) -> impl Iterator<Item = (Address, [u8; 2])> + '_ {
+        let (threshold_msb, threshold_lsb): (ThresholdMsb, ThresholdLsb) =
+            self.threshold.into_parts();
+        // GAIN2 only has writable bits on devices with more than 4 channels
+        let gain2 = (RegisterMap::<CHANNELS>::read_back_mask(Address::Gain2) != [0; 2])
+            .then_some((Address::Gain2, self.gain2.to_be_bytes()));
+
+        [
+            (Address::Mode, self.mode.to_be_bytes()),
+            (Address::Gain1, self.gain1.to_be_bytes()),
+        ]
+        .into_iter()
+        .chain(gain2)
+        .chain([
+            (Address::Config, self.config.to_be_bytes()),
+            (Address::ThresholdMsb, threshold_msb.to_be_bytes()),
+            (Address::ThresholdLsb, threshold_lsb.to_be_bytes()),
+        ])
+        .chain((0..).zip(&self.channels).filter_map(|(channel, config)| {
+            let channel = Channel::try_from(channel).ok()?;
+            Some((
+                ChannelConfig::address_for_channel(channel),
+                config.to_be_bytes(),
+            ))
+        }))
+        .chain([(Address::Clock, self.clock.to_be_bytes())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::driver::Discard;
+    use crate::spi::replay::{frame, open};
+
+    #[test]
+    fn unsupported_channel() {
+        assert_eq!(
+            AdcConfig::<4>::new()
+                .gain(Channel::Four, PgaGain::Gain2)
+                .build(),
+            Err(Error::UnsupportedChannel)
+        );
+        assert_eq!(
+            AdcConfig::<4>::new()
+                .channel_enabled(Channel::Seven, false)
+                .build(),
+            Err(Error::UnsupportedChannel)
+        );
+        assert!(AdcConfig::<8>::new()
+            .gain(Channel::Seven, PgaGain::Gain2)
+            .build()
+            .is_ok());
+    }
+
+    #[test]
+    fn invalid_combinations() {
+        assert_eq!(
+            AdcConfig::<4>::new()
+                .oversampling_ratio(OversamplingRatio::Osr256)
+                .turbo_mode(true)
+                .build(),
+            Err(Error::InvalidConfig)
+        );
+        assert_eq!(
+            AdcConfig::<8>::new().turbo_mode(true).build(),
+            Err(Error::InvalidConfig)
+        );
+        assert_eq!(
+            AdcConfig::<4>::new()
+                .power_mode(PowerMode::LowPower)
+                .build(),
+            Err(Error::InvalidConfig)
+        );
+        assert!(AdcConfig::<4>::with_clkin(NOMINAL_CLKIN_HZ / 2)
+            .power_mode(PowerMode::LowPower)
+            .build()
+            .is_ok());
+        assert_eq!(
+            AdcConfig::<4>::new().sample_rate(0.0).build(),
+            Err(Error::InvalidConfig)
+        );
+    }
+
+    #[test]
+    fn sample_rate() {
+        let clock = AdcConfig::<4>::new()
+            .sample_rate(4_000.0)
+            .build()
+            .unwrap()
+            .clock;
+        assert_eq!(clock.oversampling_ratio, OversamplingRatio::Osr1024);
+        assert!(!clock.turbo_mode);
+
+        let clock = AdcConfig::<4>::new()
+            .sample_rate(60_000.0)
+            .build()
+            .unwrap()
+            .clock;
+        assert_eq!(clock.oversampling_ratio, OversamplingRatio::Osr128);
+        assert!(clock.turbo_mode);
+
+        let clock = AdcConfig::<8>::new()
+            .sample_rate(60_000.0)
+            .build()
+            .unwrap()
+            .clock;
+        assert_eq!(clock.oversampling_ratio, OversamplingRatio::Osr128);
+        assert!(!clock.turbo_mode);
+
+        let clock = AdcConfig::<4>::with_clkin(NOMINAL_CLKIN_HZ / 4)
+            .power_mode(PowerMode::VeryLowPower)
+            .sample_rate(250.0)
+            .build()
+            .unwrap()
+            .clock;
+        assert_eq!(clock.oversampling_ratio, OversamplingRatio::Osr4096);
+    }
+
+    #[test]
+    fn commands() {
+        let registers = AdcConfig::<4>::new()
+            .gain(Channel::One, PgaGain::Gain4)
+            .global_chop(Some(GlobalChopDelay::Delay4))
+            .dc_block(DcBlock::OneOver8)
+            .channel_enabled(Channel::Three, false)
+            .drdy(
+                DrdySource::LogicOr,
+                DrdyNotReadyState::HighImpedance,
+                DrdyReadyState::LowPulse,
+            )
+            .build()
+            .unwrap();
+        assert_eq!(registers.commands().count(), 10);
+
+        let acks = [
+            [0x41, 0x00],
+            [0x42, 0x00],
+            [0x43, 0x00],
+            [0x43, 0x80],
+            [0x44, 0x00],
+            [0x44, 0x80],
+            [0x47, 0x00],
+            [0x49, 0x80],
+            [0x4C, 0x00],
+            [0x41, 0x80],
+        ];
+        let status = frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]);
+        let mut frames = std::vec![frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]])];
+        for ack in acks {
+            frames.push(frame(&[ack, [0, 0], [0, 0], [0, 0], [0, 0]]));
+            frames.push(status.clone());
+        }
+        frames.pop();
+        let mut driver = open(&frames);
+
+        registers.apply(&mut driver, &mut Discard).unwrap();
+
+        assert_eq!(driver.shadow().clock, registers.clock);
+        assert_eq!(driver.shadow().gain1.pga_gain1, PgaGain::Gain4);
+        let intf = driver.into_inner().release();
+        assert!(intf.is_done());
+        let opcodes: Vec<[u8; 2]> = intf
+            .sent
+            .iter()
+            .step_by(2)
+            .map(|frame| [frame[0], frame[1]])
+            .collect();
+        assert_eq!(
+            opcodes,
+            [
+                [0x61, 0x00],
+                [0x62, 0x00],
+                [0x63, 0x00],
+                [0x63, 0x80],
+                [0x64, 0x00],
+                [0x64, 0x80],
+                [0x67, 0x00],
+                [0x69, 0x80],
+                [0x6C, 0x00],
+                [0x61, 0x80],
+            ]
+        );
+        // Channel 3 disabled, and the rest of CLOCK at its reset value
+        assert_eq!(intf.sent[18][3..5], [0x07, 0x0E]);
+    }
+
+    #[test]
+    fn gain2_written_on_larger_devices() {
+        let registers = AdcConfig::<8>::new().build().unwrap();
+        assert_eq!(registers.commands().count(), 15);
+        assert!(registers
+            .writes()
+            .any(|(address, _)| address == Address::Gain2));
+        assert!(!AdcConfig::<4>::new()
+            .build()
+            .unwrap()
+            .writes()
+            .any(|(address, _)| address == Address::Gain2));
+    }
+}
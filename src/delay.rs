@@ -0,0 +1,67 @@
+//! Traits for supporting delay providers
+
+/// A provider of blocking delays
+pub trait Delay {
+    /// Pause execution for at least `us` microseconds
+    fn delay_us(&mut self, us: u32);
+}
+
+impl<T> Delay for T
+where
+    T: embedded_hal::blocking::delay::DelayUs<u32>,
+{
+    fn delay_us(&mut self, us: u32) {
+        embedded_hal::blocking::delay::DelayUs::delay_us(self, us);
+    }
+}
+
+/// A delay provider which actually pauses execution
+///
+/// Operations which fail if a delay is cut short, such as pulsing the `SYNC/RESET` pin,
+/// are only available with one of these. [`NoDelay`] does not implement it
+pub trait RealDelay: Delay {}
+
+impl<T> RealDelay for T where T: embedded_hal::blocking::delay::DelayUs<u32> {}
+
+/// A placeholder for a missing delay provider
+///
+/// Delays return immediately, so timing requirements between transactions are not enforced
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NoDelay;
+
+impl Delay for NoDelay {
+    fn delay_us(&mut self, _us: u32) {}
+}
+
+/// A [`Delay`] implementation for an embedded-hal 1.0 [`DelayNs`](embedded_hal_1::delay::DelayNs)
+#[cfg(feature = "embedded-hal-1")]
+#[derive(Debug)]
+pub struct DelayInterface<D> {
+    delay: D,
+}
+
+#[cfg(feature = "embedded-hal-1")]
+impl<D> DelayInterface<D> {
+    /// Create a new interface using a delay provider
+    pub const fn new(delay: D) -> Self {
+        Self { delay }
+    }
+
+    /// Destroy the interface and return the delay provider
+    pub fn release(self) -> D {
+        self.delay
+    }
+}
+
+#[cfg(feature = "embedded-hal-1")]
+impl<D> Delay for DelayInterface<D>
+where
+    D: embedded_hal_1::delay::DelayNs,
+{
+    fn delay_us(&mut self, us: u32) {
+        self.delay.delay_us(us);
+    }
+}
+
+#[cfg(feature = "embedded-hal-1")]
+impl<D> RealDelay for DelayInterface<D> where D: embedded_hal_1::delay::DelayNs {}
@@ -0,0 +1,136 @@
+//! Traits for supporting digital IO pins
+
+use crate::Error;
+
+/// A digital input pin
+pub trait InputPin {
+    /// Check if the pin is driven low
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the pin could not be read
+    fn is_low(&mut self) -> Result<bool, Error>;
+}
+
+impl<T> InputPin for T
+where
+    T: embedded_hal::digital::v2::InputPin,
+{
+    fn is_low(&mut self) -> Result<bool, Error> {
+        embedded_hal::digital::v2::InputPin::is_low(self).map_err(|_| Error::PinIOError)
+    }
+}
+
+/// A digital output pin
+pub trait OutputPin {
+    /// Drive the pin low
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the pin could not be set
+    fn set_low(&mut self) -> Result<(), Error>;
+
+    /// Drive the pin high
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the pin could not be set
+    fn set_high(&mut self) -> Result<(), Error>;
+}
+
+impl<T> OutputPin for T
+where
+    T: embedded_hal::digital::v2::OutputPin,
+{
+    fn set_low(&mut self) -> Result<(), Error> {
+        embedded_hal::digital::v2::OutputPin::set_low(self).map_err(|_| Error::PinIOError)
+    }
+
+    fn set_high(&mut self) -> Result<(), Error> {
+        embedded_hal::digital::v2::OutputPin::set_high(self).map_err(|_| Error::PinIOError)
+    }
+}
+
+/// A placeholder for a pin which is not connected
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NoPin;
+
+/// An [`InputPin`] and [`OutputPin`] implementation for an embedded-hal 1.0 [`InputPin`](embedded_hal_1::digital::InputPin)
+/// or [`OutputPin`](embedded_hal_1::digital::OutputPin)
+#[cfg(feature = "embedded-hal-1")]
+#[derive(Debug)]
+pub struct PinInterface<P> {
+    pin: P,
+}
+
+#[cfg(feature = "embedded-hal-1")]
+impl<P> PinInterface<P> {
+    /// Create a new interface using a pin
+    pub const fn new(pin: P) -> Self {
+        Self { pin }
+    }
+
+    /// Destroy the interface and return the pin
+    pub fn release(self) -> P {
+        self.pin
+    }
+}
+
+#[cfg(feature = "embedded-hal-1")]
+impl<P> InputPin for PinInterface<P>
+where
+    P: embedded_hal_1::digital::InputPin,
+{
+    fn is_low(&mut self) -> Result<bool, Error> {
+        self.pin.is_low().map_err(|_| Error::PinIOError)
+    }
+}
+
+#[cfg(feature = "embedded-hal-1")]
+impl<P> OutputPin for PinInterface<P>
+where
+    P: embedded_hal_1::digital::OutputPin,
+{
+    fn set_low(&mut self) -> Result<(), Error> {
+        self.pin.set_low().map_err(|_| Error::PinIOError)
+    }
+
+    fn set_high(&mut self) -> Result<(), Error> {
+        self.pin.set_high().map_err(|_| Error::PinIOError)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod replay {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::InputPin;
+    use crate::Error;
+
+    /// An input pin which replays a list of levels, one per read
+    pub struct ReplayPin {
+        levels: Vec<bool>,
+    }
+
+    impl ReplayPin {
+        /// Build a replay pin from a list of levels, where `true` is high
+        pub fn new(levels: &[bool]) -> Self {
+            Self {
+                levels: levels.iter().rev().copied().collect(),
+            }
+        }
+
+        /// Check if every level has been replayed
+        pub fn is_done(&self) -> bool {
+            self.levels.is_empty()
+        }
+    }
+
+    impl InputPin for ReplayPin {
+        fn is_low(&mut self) -> Result<bool, Error> {
+            Ok(!self.levels.pop().expect("unexpected pin read"))
+        }
+    }
+}
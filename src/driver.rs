@@ -0,0 +1,1087 @@
+//! High level blocking device driver
+
+use crate::delay::{Delay, NoDelay, RealDelay};
+use crate::digital::{NoPin, OutputPin};
+use crate::interface::{Ads131m, Command, LinkStats, RegisterBlock, Response, SampleGrab};
+use crate::register::{Address, Channel, ChannelSpecific, Global, Id, Mode};
+use crate::register_map::{AddressSet, RegisterMap};
+use crate::spi::Transfer;
+use crate::timing::NOMINAL_CLKIN_HZ;
+use crate::Error;
+
+/// Time for the registers to stabilize after a reset, in microseconds
+pub const RESET_SETTLING_US: u32 = 5;
+
+/// The number of `CLKIN` periods the `SYNC/RESET` pin must be held low to reset the device
+pub const RESET_PULSE_CLKIN_PERIODS: u32 = 2048;
+
+/// Length of the `SYNC/RESET` low pulse used to reset the device with the nominal `CLKIN`, in microseconds
+///
+/// The device resets once the pin is held low for 2048 `CLKIN` periods,
+/// which is 250us with the nominal 8.192 megahertz clock
+pub const RESET_PULSE_US: u32 = reset_pulse_us(NOMINAL_CLKIN_HZ);
+
+/// Length of the `SYNC/RESET` low pulse used to resynchronize the device, in microseconds
+///
+/// This must be at least one `CLKIN` period, and shorter than a reset pulse
+pub const SYNC_PULSE_US: u32 = 1;
+
+/// Length of the `SYNC/RESET` low pulse used to reset a device clocked at `clkin_hz`, in microseconds
+///
+/// This is [`RESET_PULSE_CLKIN_PERIODS`] with a 20% margin, rounded up
+#[must_use]
+#[allow(clippy::cast_possible_truncation)]
+pub const fn reset_pulse_us(clkin_hz: u32) -> u32 {
+    let clkin_hz = if clkin_hz == 0 { 1 } else { clkin_hz as u64 };
+    let us = (RESET_PULSE_CLKIN_PERIODS as u64 * 1_200_000).div_ceil(clkin_hz);
+
+    if us > u32::MAX as u64 {
+        u32::MAX
+    } else {
+        us as u32
+    }
+}
+
+/// A receiver for the sample grabs collected while the driver performs other operations
+pub trait SampleSink<const CHANNELS: usize> {
+    /// Receive a sample grab
+    fn push(&mut self, sample_grab: SampleGrab<CHANNELS>);
+}
+
+impl<F, const CHANNELS: usize> SampleSink<CHANNELS> for F
+where
+    F: FnMut(SampleGrab<CHANNELS>),
+{
+    fn push(&mut self, sample_grab: SampleGrab<CHANNELS>) {
+        self(sample_grab);
+    }
+}
+
+/// A [`SampleSink`] which drops every sample grab it receives
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Discard;
+
+impl<const CHANNELS: usize> SampleSink<CHANNELS> for Discard {
+    fn push(&mut self, _sample_grab: SampleGrab<CHANNELS>) {}
+}
+
+/// High level blocking device driver
+///
+/// This wraps an [`Ads131m`] interface and hides the one frame lag between a command and its response.
+/// Each operation sends its command followed by a null command, and checks the response
+/// returned in the second exchange.
+///
+/// Any sample grabs received along the way are forwarded to the [`SampleSink`] passed to each operation,
+/// so no samples are lost while the device is being configured.
+///
+/// The driver can optionally own the `SYNC/RESET` pin and a [`Delay`] provider,
+/// which are added with [`with_sync_reset_pin`](Self::with_sync_reset_pin) and [`with_delay`](Self::with_delay).
+///
+/// # Shadow register map
+///
+/// The driver keeps a shadow copy of every register it has read or written, available from
+/// [`shadow`](Self::shadow). Registers only written with raw commands through [`execute`](Self::execute)
+/// are not tracked. After a reset, every register is known to hold its default value.
+pub struct Driver<S: Transfer<W>, W: Copy, const CHANNELS: usize, P = NoPin, D = NoDelay> {
+    adc: Ads131m<S, W, CHANNELS>,
+    sync_reset: P,
+    delay: D,
+    clkin_hz: u32,
+    shadow: RegisterMap<CHANNELS>,
+    shadowed: AddressSet,
+}
+
+impl<S, W, const CHANNELS: usize> Driver<S, W, CHANNELS>
+where
+    S: Transfer<W>,
+    W: Copy,
+{
+    /// Create a new driver using an opened [`Ads131m`] interface
+    ///
+    /// The first operation will decode the response to the last command sent through `adc`
+    pub fn new(adc: Ads131m<S, W, CHANNELS>) -> Self {
+        Self {
+            adc,
+            sync_reset: NoPin,
+            delay: NoDelay,
+            clkin_hz: NOMINAL_CLKIN_HZ,
+            shadow: RegisterMap::default(),
+            shadowed: AddressSet::new(),
+        }
+    }
+
+    /// Create a new driver, and check that the device matches the opened model
+    ///
+    /// The `ID` register is read to find the channel count of the device
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device is a different model than `adc` was opened for
+    pub fn new_checked(
+        adc: Ads131m<S, W, CHANNELS>,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<Self, Error> {
+        let mut driver = Self::new(adc);
+        driver.check_model(sink)?;
+
+        Ok(driver)
+    }
+}
+
+impl<S, W, const CHANNELS: usize, P, D> Driver<S, W, CHANNELS, P, D>
+where
+    S: Transfer<W>,
+    W: Copy,
+    D: Delay,
+{
+    /// Give the driver control of the `SYNC/RESET` pin
+    ///
+    /// The pin should already be driven high. Pulsing it with [`hardware_reset`](Self::hardware_reset)
+    /// or [`resync`](Self::resync) also needs a [`RealDelay`] provider from [`with_delay`](Self::with_delay)
+    pub fn with_sync_reset_pin<P2: OutputPin>(self, pin: P2) -> Driver<S, W, CHANNELS, P2, D> {
+        Driver {
+            adc: self.adc,
+            sync_reset: pin,
+            delay: self.delay,
+            clkin_hz: self.clkin_hz,
+            shadow: self.shadow,
+            shadowed: self.shadowed,
+        }
+    }
+
+    /// Give the driver a delay provider, used to wait for the device to settle after a reset,
+    /// and for new conversions during calibration
+    pub fn with_delay<D2: Delay>(self, delay: D2) -> Driver<S, W, CHANNELS, P, D2> {
+        Driver {
+            adc: self.adc,
+            sync_reset: self.sync_reset,
+            delay,
+            clkin_hz: self.clkin_hz,
+            shadow: self.shadow,
+            shadowed: self.shadowed,
+        }
+    }
+
+    /// Set the `CLKIN` frequency of the device, used to time the reset pulse and waits for new conversions
+    ///
+    /// This is [`NOMINAL_CLKIN_HZ`] by default
+    #[must_use]
+    pub const fn with_clkin(mut self, clkin_hz: u32) -> Self {
+        self.clkin_hz = clkin_hz;
+        self
+    }
+
+    /// The `CLKIN` frequency of the device, in hertz
+    pub const fn clkin_hz(&self) -> u32 {
+        self.clkin_hz
+    }
+
+    /// Release the underlying [`Ads131m`] interface
+    pub fn into_inner(self) -> Ads131m<S, W, CHANNELS> {
+        self.adc
+    }
+
+    /// Release the underlying [`Ads131m`] interface, `SYNC/RESET` pin and delay provider
+    pub fn release(self) -> (Ads131m<S, W, CHANNELS>, P, D) {
+        (self.adc, self.sync_reset, self.delay)
+    }
+
+    /// Send a command and return the device's response to it
+    ///
+    /// The command is followed by a null command to collect the response.
+    /// Sample grabs from both exchanges are forwarded to `sink`, so the returned
+    /// [`Response`] will never contain a sample grab.
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed
+    pub fn execute(
+        &mut self,
+        command: Command,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<Response<CHANNELS>, Error> {
+        let _ = self.exchange(command, sink)?;
+        self.exchange(Command::new_null(), sink)
+    }
+
+    /// Read the next sample grab from the device
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not return a sample grab
+    pub fn read_sample_grab(&mut self) -> Result<SampleGrab<CHANNELS>, Error> {
+        self.adc
+            .communicate(Command::new_null())?
+            .sample_grab
+            .ok_or(Error::UnexpectedResponse)
+    }
+
+    /// Exchange a null command and return the whole frame received
+    ///
+    /// Every operation ends with a null command, so the frame contains
+    /// the status word along with the next sample grab
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed
+    pub fn read_frame(&mut self) -> Result<Response<CHANNELS>, Error> {
+        self.adc.communicate(Command::new_null())
+    }
+
+    /// Read a global device register
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device responded with data from a different register
+    pub fn read_register<R: Global>(
+        &mut self,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<R, Error> {
+        self.read_raw(R::ADDRESS, sink).map(R::from_be_bytes)
+    }
+
+    /// Check that the device matches the model the driver was opened for
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the `ID` register reports a different channel count
+    pub fn check_model(&mut self, sink: &mut impl SampleSink<CHANNELS>) -> Result<(), Error> {
+        let id: Id = self.read_register(sink)?;
+        if usize::from(id.channel_count) != CHANNELS {
+            return Err(Error::ModelMismatch {
+                expected: CHANNELS,
+                found: usize::from(id.channel_count),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Read a channel-specific device register
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device responded with data from a different register
+    pub fn read_channel_register<R: ChannelSpecific>(
+        &mut self,
+        channel: Channel,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<R, Error> {
+        self.read_raw(R::address_for_channel(channel), sink)
+            .map(R::from_be_bytes)
+    }
+
+    /// Read `count` consecutive device registers, starting at `address`
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the register range is invalid, communication with the device failed,
+    /// or the device responded with data from different registers
+    pub fn read_registers(
+        &mut self,
+        address: Address,
+        count: u8,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<RegisterBlock, Error> {
+        let resp = self.execute(Command::new_read_registers(address, count)?, sink)?;
+
+        // Single register reads are not returned as a block
+        let block = match (resp.register_block, resp.register_read) {
+            (Some(block), _) => block,
+            (None, Some(read)) => RegisterBlock::from(read),
+            (None, None) => return Err(Error::UnexpectedResponse),
+        };
+
+        if block.address() != address || block.len() != usize::from(count) {
+            return Err(Error::UnexpectedResponse);
+        }
+
+        for read in block.iter() {
+            self.update_shadow(read.address, read.data);
+        }
+
+        Ok(block)
+    }
+
+    /// Write a global device register
+    ///
+    /// If the device's [`Mode`] register is written,
+    /// the driver will switch to the new communication settings
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not acknowledge the write
+    pub fn write_register<R: Global>(
+        &mut self,
+        register: R,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error> {
+        let _ = self.execute(Command::new_write_global_register(register), sink)?;
+        self.update_shadow(R::ADDRESS, register.to_be_bytes());
+
+        Ok(())
+    }
+
+    /// Write a channel-specific device register
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not acknowledge the write
+    pub fn write_channel_register<R: ChannelSpecific>(
+        &mut self,
+        register: R,
+        channel: Channel,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error> {
+        let _ = self.execute(Command::new_write_channel_register(register, channel), sink)?;
+        self.update_shadow(R::address_for_channel(channel), register.to_be_bytes());
+
+        Ok(())
+    }
+
+    /// Write a global device register, then read it back to verify the write
+    ///
+    /// Only the bits in [`Global::read_back_mask`] for this device model are compared
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Error::VerifyMismatch`] if the register did not read back the written value.
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not acknowledge the write
+    pub fn write_verified<R: Global>(
+        &mut self,
+        register: R,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error> {
+        self.write_register(register, sink)?;
+        self.verify(
+            R::ADDRESS,
+            register.to_be_bytes(),
+            R::read_back_mask(CHANNELS),
+            sink,
+        )
+    }
+
+    /// Write a channel-specific device register, then read it back to verify the write
+    ///
+    /// Only the bits in [`ChannelSpecific::read_back_mask`] for this device model are compared
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Error::VerifyMismatch`] if the register did not read back the written value.
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not acknowledge the write
+    pub fn write_channel_verified<R: ChannelSpecific>(
+        &mut self,
+        register: R,
+        channel: Channel,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error> {
+        self.write_channel_register(register, channel, sink)?;
+        self.verify(
+            R::address_for_channel(channel),
+            register.to_be_bytes(),
+            R::read_back_mask(CHANNELS),
+            sink,
+        )
+    }
+
+    /// Write consecutive device registers, starting at `address`
+    ///
+    /// Words are MSB first
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the register range is invalid, communication with the device failed,
+    /// or the device did not acknowledge the write
+    pub fn write_registers(
+        &mut self,
+        address: Address,
+        words: &[[u8; 2]],
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error> {
+        let _ = self.execute(Command::new_write_registers(address, words)?, sink)?;
+        for (offset, &word) in (0..).zip(words) {
+            if let Some(address) = Address::from_address(address.address() + offset) {
+                self.update_shadow(address, word);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reset the device
+    ///
+    /// The driver will switch back to the default communication settings,
+    /// and wait for the registers to stabilize before acknowledging the reset
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not acknowledge the reset
+    pub fn reset(&mut self, sink: &mut impl SampleSink<CHANNELS>) -> Result<(), Error> {
+        let _ = self.exchange(Command::new_reset(), sink)?;
+        self.delay.delay_us(RESET_SETTLING_US);
+        let _ = self.exchange(Command::new_null(), sink)?;
+
+        self.reset_shadow();
+        Ok(())
+    }
+
+    /// Place the device in a low power standby mode
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not acknowledge the command
+    pub fn standby(&mut self, sink: &mut impl SampleSink<CHANNELS>) -> Result<(), Error> {
+        self.execute(Command::new_standby(), sink).map(|_| ())
+    }
+
+    /// Wake the device from standby mode
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not acknowledge the command
+    pub fn wakeup(&mut self, sink: &mut impl SampleSink<CHANNELS>) -> Result<(), Error> {
+        self.execute(Command::new_wakeup(), sink).map(|_| ())
+    }
+
+    /// Lock the device to only respond to the `null`, `read_register`, and `unlock` commands
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not acknowledge the command
+    pub fn lock(&mut self, sink: &mut impl SampleSink<CHANNELS>) -> Result<(), Error> {
+        self.execute(Command::new_lock(), sink).map(|_| ())
+    }
+
+    /// Unlock the device after it has been locked
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not acknowledge the command
+    pub fn unlock(&mut self, sink: &mut impl SampleSink<CHANNELS>) -> Result<(), Error> {
+        self.execute(Command::new_unlock(), sink).map(|_| ())
+    }
+
+    /// Find the communication settings of the device by probing every word length and CRC type
+    ///
+    /// Use this when the device may have changed its settings without the driver knowing,
+    /// such as after a brown-out. Returns the `MODE` register read from the device
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed with every setting
+    pub fn detect_mode(&mut self) -> Result<Mode, Error> {
+        let mode = self.adc.detect_mode()?;
+        self.update_shadow(Address::Mode, mode.to_be_bytes());
+
+        Ok(mode)
+    }
+
+    /// The communication health counters, as kept by the interface
+    #[must_use]
+    pub const fn link_stats(&self) -> LinkStats {
+        self.adc.link_stats()
+    }
+
+    /// Reset every communication health counter to zero
+    pub fn clear_link_stats(&mut self) {
+        self.adc.clear_link_stats();
+    }
+
+    /// The shadow copy of the device registers
+    ///
+    /// Only the registers in [`shadowed`](Self::shadowed) are known to match the device
+    pub const fn shadow(&self) -> &RegisterMap<CHANNELS> {
+        &self.shadow
+    }
+
+    /// The registers whose value in the [`shadow`](Self::shadow) is known
+    pub const fn shadowed(&self) -> AddressSet {
+        self.shadowed
+    }
+
+    /// Modify a global device register using its shadow value
+    ///
+    /// The register is only read from the device if it is not already shadowed
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not acknowledge the write
+    pub fn modify<R: Global>(
+        &mut self,
+        f: impl FnOnce(&mut R),
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error> {
+        let mut register = R::from_be_bytes(self.shadowed_raw(R::ADDRESS, sink)?);
+        f(&mut register);
+        self.write_register(register, sink)
+    }
+
+    /// Modify a channel-specific device register using its shadow value
+    ///
+    /// The register is only read from the device if it is not already shadowed
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not acknowledge the write
+    pub fn modify_channel<R: ChannelSpecific>(
+        &mut self,
+        channel: Channel,
+        f: impl FnOnce(&mut R),
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error> {
+        let address = R::address_for_channel(channel);
+        let mut register = R::from_be_bytes(self.shadowed_raw(address, sink)?);
+        f(&mut register);
+        self.write_channel_register(register, channel, sink)
+    }
+
+    /// Read the whole register map from the device into the shadow
+    ///
+    /// Returns the shadowed registers which did not match the device
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed
+    pub fn resync_shadow(
+        &mut self,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<AddressSet, Error> {
+        let before = self.shadow;
+        let shadowed = self.shadowed;
+        let map = RegisterMap::<CHANNELS>::read(self, sink)?;
+
+        Ok(RegisterMap::<CHANNELS>::addresses()
+            .filter(|&address| {
+                shadowed.contains(address) && before.get(address) != map.get(address)
+            })
+            .collect())
+    }
+
+    /// Read the raw value of a single register
+    fn read_raw(
+        &mut self,
+        address: Address,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<[u8; 2], Error> {
+        match self
+            .execute(Command::new_read_register(address), sink)?
+            .register_read
+        {
+            Some(read) if read.address == address => {
+                self.update_shadow(address, read.data);
+                Ok(read.data)
+            }
+            _ => Err(Error::UnexpectedResponse),
+        }
+    }
+
+    /// Get the raw value of a single register from the shadow, or the device if it is not shadowed
+    pub(crate) fn shadowed_raw(
+        &mut self,
+        address: Address,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<[u8; 2], Error> {
+        match self.shadow.get(address) {
+            Some(word) if self.shadowed.contains(address) => Ok(word),
+            _ => self.read_raw(address, sink),
+        }
+    }
+
+    /// Pause for at least `us` microseconds using the delay provider
+    pub(crate) fn delay_us(&mut self, us: u32) {
+        self.delay.delay_us(us);
+    }
+
+    fn update_shadow(&mut self, address: Address, word: [u8; 2]) {
+        if self.shadow.set(address, word) {
+            self.shadowed.insert(address);
+        }
+    }
+
+    /// Every register holds its default value after a reset, as it reads back from the device
+    pub(crate) fn reset_shadow(&mut self) {
+        self.shadow = RegisterMap::default().masked();
+        self.shadowed = RegisterMap::<CHANNELS>::addresses().collect();
+    }
+
+    /// Read back a register and compare the bits in `mask` against the value written to it
+    fn verify(
+        &mut self,
+        address: Address,
+        wrote: [u8; 2],
+        mask: [u8; 2],
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error> {
+        let read = self.read_raw(address, sink)?;
+        if wrote[0] & mask[0] != read[0] & mask[0] || wrote[1] & mask[1] != read[1] & mask[1] {
+            return Err(Error::VerifyMismatch {
+                address,
+                wrote,
+                read,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Perform a single exchange, forwarding any sample grab to `sink`
+    fn exchange(
+        &mut self,
+        command: Command,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<Response<CHANNELS>, Error> {
+        let mut resp = self.adc.communicate(command)?;
+        if let Some(sample_grab) = resp.sample_grab.take() {
+            sink.push(sample_grab);
+        }
+
+        Ok(resp)
+    }
+}
+
+impl<S, W, const CHANNELS: usize, P, D> Driver<S, W, CHANNELS, P, D>
+where
+    S: Transfer<W>,
+    W: Copy,
+    P: OutputPin,
+    D: RealDelay,
+{
+    /// Reset the device by holding the `SYNC/RESET` pin low
+    ///
+    /// The pulse length is found from the `CLKIN` frequency set with [`with_clkin`](Self::with_clkin).
+    /// The driver will switch back to the default communication settings,
+    /// and wait for the registers to stabilize.
+    /// The device acknowledges the reset in the next exchange
+    ///
+    /// This needs a [`RealDelay`] provider to time the pulse, so it is not available
+    /// to a driver with only a `SYNC/RESET` pin:
+    ///
+    /// ```compile_fail
+    /// # use ads131m::digital::OutputPin;
+    /// # use ads131m::driver::Driver;
+    /// # use ads131m::interface::Ads131m;
+    /// # use ads131m::spi::Transfer;
+    /// # use ads131m::Error;
+    /// # struct Spi;
+    /// # impl Transfer<u8> for Spi {
+    /// #     fn transfer(&mut self, _send: &[u8], _receive: &mut [u8]) -> Result<(), Error> { Ok(()) }
+    /// # }
+    /// # struct Pin;
+    /// # impl OutputPin for Pin {
+    /// #     fn set_low(&mut self) -> Result<(), Error> { Ok(()) }
+    /// #     fn set_high(&mut self) -> Result<(), Error> { Ok(()) }
+    /// # }
+    /// let mut driver = Driver::new(Ads131m::open_ads131m04(Spi)).with_sync_reset_pin(Pin);
+    /// driver.hardware_reset()?;
+    /// # Ok::<(), Error>(())
+    /// ```
+    ///
+    /// ```
+    /// # use ads131m::digital::OutputPin;
+    /// # use ads131m::driver::Driver;
+    /// # use ads131m::interface::Ads131m;
+    /// # use ads131m::spi::Transfer;
+    /// # use ads131m::Error;
+    /// # struct Spi;
+    /// # impl Transfer<u8> for Spi {
+    /// #     fn transfer(&mut self, _send: &[u8], _receive: &mut [u8]) -> Result<(), Error> { Ok(()) }
+    /// # }
+    /// # struct Pin;
+    /// # impl OutputPin for Pin {
+    /// #     fn set_low(&mut self) -> Result<(), Error> { Ok(()) }
+    /// #     fn set_high(&mut self) -> Result<(), Error> { Ok(()) }
+    /// # }
+    /// # struct Delay;
+    /// # impl embedded_hal::blocking::delay::DelayUs<u32> for Delay {
+    /// #     fn delay_us(&mut self, _us: u32) {}
+    /// # }
+    /// let mut driver = Driver::new(Ads131m::open_ads131m04(Spi))
+    ///     .with_sync_reset_pin(Pin)
+    ///     .with_delay(Delay);
+    /// driver.hardware_reset()?;
+    /// # Ok::<(), Error>(())
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the `SYNC/RESET` pin could not be set
+    pub fn hardware_reset(&mut self) -> Result<(), Error> {
+        self.sync_reset.set_low()?;
+        self.delay.delay_us(reset_pulse_us(self.clkin_hz));
+        self.sync_reset.set_high()?;
+        self.delay.delay_us(RESET_SETTLING_US);
+
+        self.adc.reset_state();
+        self.reset_shadow();
+        Ok(())
+    }
+
+    /// Resynchronize the device by pulsing the `SYNC/RESET` pin low
+    ///
+    /// This restarts conversions on every channel, without changing any registers.
+    /// Like [`hardware_reset`](Self::hardware_reset), this needs a [`RealDelay`] provider
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the `SYNC/RESET` pin could not be set
+    pub fn resync(&mut self) -> Result<(), Error> {
+        self.sync_reset.set_low()?;
+        self.delay.delay_us(SYNC_PULSE_US);
+        self.sync_reset.set_high()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::register::{Clock, Gain1, OversamplingRatio, PgaGain};
+    use crate::spi::replay::{frame, open};
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum Event {
+        Low,
+        High,
+        Delay(u32),
+    }
+
+    /// Records pin changes and delays in a shared log
+    #[derive(Clone, Default)]
+    struct Recorder(Rc<RefCell<Vec<Event>>>);
+
+    impl OutputPin for Recorder {
+        fn set_low(&mut self) -> Result<(), Error> {
+            self.0.borrow_mut().push(Event::Low);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Error> {
+            self.0.borrow_mut().push(Event::High);
+            Ok(())
+        }
+    }
+
+    impl Delay for Recorder {
+        fn delay_us(&mut self, us: u32) {
+            self.0.borrow_mut().push(Event::Delay(us));
+        }
+    }
+
+    impl RealDelay for Recorder {}
+
+    #[test]
+    fn read_register() {
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0x00, 0x01], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x0F, 0x0E]]),
+        ]);
+
+        let mut grabs = Vec::new();
+        let clock: Clock = driver
+            .read_register(&mut |grab: SampleGrab<4>| grabs.push(grab))
+            .unwrap();
+        assert_eq!(
+            clock,
+            Clock {
+                channel4_en: false,
+                channel5_en: false,
+                channel6_en: false,
+                channel7_en: false,
+                ..Clock::default()
+            }
+        );
+        assert_eq!(grabs.len(), 1);
+        assert_eq!(grabs[0].clone().into_i32_array(), [256, 0, 0, 0]);
+
+        let intf = driver.into_inner().release();
+        assert_eq!(intf.sent[0][..3], [0xA1, 0x80, 0x00]);
+        assert_eq!(intf.sent[1][..3], [0x00, 0x00, 0x00]);
+        assert!(intf.is_done());
+    }
+
+    #[test]
+    fn write_register() {
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x42, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+        ]);
+
+        let mut count = 0;
+        let gain = Gain1 {
+            pga_gain0: PgaGain::Gain32,
+            ..Gain1::default()
+        };
+        driver
+            .write_register(gain, &mut |_: SampleGrab<4>| count += 1)
+            .unwrap();
+        assert_eq!(count, 2);
+
+        let intf = driver.into_inner().release();
+        assert_eq!(intf.sent[0][..6], [0x62, 0x00, 0x00, 0x00, 0x05, 0x00]);
+    }
+
+    #[test]
+    fn write_register_not_acknowledged() {
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+        ]);
+
+        assert_eq!(
+            driver.write_register(Gain1::default(), &mut Discard),
+            Err(Error::UnexpectedResponse)
+        );
+    }
+
+    #[test]
+    fn reset() {
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+        ]);
+
+        let mut count = 0;
+        let _ = driver.read_sample_grab().unwrap();
+        driver.reset(&mut |_: SampleGrab<4>| count += 1).unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn write_verified() {
+        let gain = Gain1 {
+            pga_gain2: PgaGain::Gain4,
+            ..Gain1::default()
+        };
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x42, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            // Reserved bits are ignored
+            frame(&[[0x8A, 0x88]]),
+            frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x42, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x03, 0x00]]),
+        ]);
+
+        driver.write_verified(gain, &mut Discard).unwrap();
+        assert_eq!(
+            driver.write_verified(gain, &mut Discard),
+            Err(Error::VerifyMismatch {
+                address: Address::Gain1,
+                wrote: [0x02, 0x00],
+                read: [0x03, 0x00],
+            })
+        );
+    }
+
+    #[test]
+    fn reset_settling_delay() {
+        let recorder = Recorder::default();
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+        ])
+        .with_delay(recorder.clone());
+
+        driver.reset(&mut Discard).unwrap();
+        assert_eq!(*recorder.0.borrow(), [Event::Delay(RESET_SETTLING_US)]);
+    }
+
+    #[test]
+    fn hardware_reset() {
+        let recorder = Recorder::default();
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+        ])
+        .with_sync_reset_pin(recorder.clone())
+        .with_delay(recorder.clone());
+
+        let _ = driver.read_sample_grab().unwrap();
+        let _ = driver.read_sample_grab().unwrap();
+        driver.hardware_reset().unwrap();
+        assert_eq!(
+            *recorder.0.borrow(),
+            [
+                Event::Low,
+                Event::Delay(RESET_PULSE_US),
+                Event::High,
+                Event::Delay(RESET_SETTLING_US)
+            ]
+        );
+
+        // The next frame acknowledges the reset
+        let resp = driver.read_frame().unwrap();
+        assert!(resp.status.is_none());
+
+        recorder.0.borrow_mut().clear();
+        driver.resync().unwrap();
+        assert_eq!(
+            *recorder.0.borrow(),
+            [Event::Low, Event::Delay(SYNC_PULSE_US), Event::High]
+        );
+    }
+
+    #[test]
+    fn reset_pulse() {
+        assert_eq!(RESET_PULSE_US, 300);
+        assert_eq!(reset_pulse_us(NOMINAL_CLKIN_HZ / 4), 1200);
+        assert_eq!(reset_pulse_us(1_000_000), 2458);
+
+        let recorder = Recorder::default();
+        let mut driver = open(&[])
+            .with_sync_reset_pin(recorder.clone())
+            .with_delay(recorder.clone())
+            .with_clkin(NOMINAL_CLKIN_HZ / 4);
+        driver.hardware_reset().unwrap();
+        assert_eq!(recorder.0.borrow()[1], Event::Delay(1200));
+    }
+
+    #[test]
+    fn check_model() {
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x24, 0x00]]),
+            frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x28, 0x00]]),
+        ]);
+
+        driver.check_model(&mut Discard).unwrap();
+        assert_eq!(
+            driver.check_model(&mut Discard),
+            Err(Error::ModelMismatch {
+                expected: 4,
+                found: 8
+            })
+        );
+    }
+
+    #[test]
+    fn shadow() {
+        let clock = Clock {
+            channel3_en: false,
+            ..Clock::default()
+        };
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x41, 0x80], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x00, 0x05]]),
+        ]);
+        assert!(driver.shadowed().is_empty());
+
+        driver.write_register(clock, &mut Discard).unwrap();
+        let gain: Gain1 = driver.read_register(&mut Discard).unwrap();
+
+        assert_eq!(driver.shadow().clock, clock);
+        assert_eq!(driver.shadow().gain1, gain);
+        assert_eq!(
+            driver.shadowed().iter().collect::<Vec<_>>(),
+            [Address::Clock, Address::Gain1]
+        );
+    }
+
+    #[test]
+    fn modify() {
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[Clock::default().to_be_bytes()]),
+            frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x41, 0x80], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x41, 0x80], [0, 0], [0, 0], [0, 0], [0, 0]]),
+        ]);
+
+        // The first modification reads the register, the second uses the shadow
+        driver
+            .modify::<Clock>(|c| c.channel0_en = false, &mut Discard)
+            .unwrap();
+        driver
+            .modify::<Clock>(
+                |c| c.oversampling_ratio = OversamplingRatio::Osr128,
+                &mut Discard,
+            )
+            .unwrap();
+
+        let intf = driver.into_inner().release();
+        assert!(intf.is_done());
+        assert_eq!(intf.sent[0][..3], [0xA1, 0x80, 0x00]);
+        assert_eq!(intf.sent[2][..6], [0x61, 0x80, 0x00, 0xFE, 0x0E, 0x00]);
+        assert_eq!(intf.sent[4][..6], [0x61, 0x80, 0x00, 0xFE, 0x02, 0x00]);
+    }
+
+    #[test]
+    fn resync_shadow() {
+        let clock = Clock {
+            channel3_en: false,
+            ..Clock::default()
+        };
+        let mut device = RegisterMap::<4>::default();
+        device.gain1.pga_gain0 = PgaGain::Gain2;
+
+        let mut block = Vec::from([[0xE1, 0x1A]]);
+        block.extend(device.words());
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x41, 0x80], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&block),
+        ]);
+
+        driver.write_register(clock, &mut Discard).unwrap();
+        let differences = driver.resync_shadow(&mut Discard).unwrap();
+
+        // GAIN1 was not shadowed, so it is not a difference
+        assert_eq!(differences.iter().collect::<Vec<_>>(), [Address::Clock]);
+        assert_eq!(driver.shadow(), &device.masked());
+        assert_eq!(driver.shadowed().len(), 27);
+    }
+
+    #[test]
+    fn reset_shadow() {
+        let mut block = Vec::from([[0xE1, 0x1A]]);
+        block.extend(RegisterMap::<4>::default().words());
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&block),
+        ]);
+
+        driver.reset(&mut Discard).unwrap();
+        assert_eq!(driver.shadow(), &RegisterMap::default().masked());
+        // Reserved CLOCK bits and GAIN2 read zero on an ADS131M04
+        assert_eq!(driver.shadow().get(Address::Clock), Some([0x0F, 0x0E]));
+        assert_eq!(driver.shadow().get(Address::Gain2), Some([0x00, 0x00]));
+        assert_eq!(driver.shadowed().len(), 27);
+
+        // The reset image matches the registers read back from the device
+        assert!(driver.resync_shadow(&mut Discard).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,596 @@
+//! Fault injection for SPI transfers, for testing error handling without hardware
+//!
+//! [`FaultInjector`] wraps any [`Transfer`] and corrupts the frames passing through it,
+//! either on a fixed schedule or at random with a configured rate.
+//! Random faults come from a seeded generator, so a failing test can be replayed exactly
+
+use crate::interface::MAX_WRITE_LEN;
+use crate::register::WordLength;
+use crate::spi::Transfer;
+use crate::Error;
+
+/// The maximum number of faults that can be scheduled at once
+pub const MAX_SCHEDULED_FAULTS: usize = 16;
+
+/// The seed used by [`FaultInjector::new`]
+const DEFAULT_SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+/// A fault to inject into a single transfer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Invert a bit of the frame sent to the device, counting from the MSB of the first byte
+    FlipSentBit {
+        /// The index of the bit to invert
+        bit: usize,
+    },
+    /// Invert a bit of the frame received from the device, counting from the MSB of the first byte
+    FlipReceivedBit {
+        /// The index of the bit to invert
+        bit: usize,
+    },
+    /// Remove a word from the received frame, shifting the following words forward
+    DropWord {
+        /// The index of the word to remove
+        word: usize,
+    },
+    /// Insert a word of zeros into the received frame, shifting the following words back
+    ExtraWord {
+        /// The index the word is inserted at
+        word: usize,
+    },
+    /// Stop the received frame early, with the remaining bytes reading zero
+    Truncate {
+        /// The number of bytes received
+        len: usize,
+    },
+    /// Reset the device just before the transfer, as a brownout or glitch on `SYNC/RESET` would
+    ///
+    /// This needs a reset hook, set with [`FaultInjector::with_reset`]. Without one the fault has no effect
+    DeviceReset,
+    /// Fail the transfer with [`Error::SpiIOError`] without communicating with the device
+    SpiError,
+}
+
+/// A kind of fault, injected at random by [`FaultInjector::set_rate`]
+///
+/// The bit, word, or length of each fault is chosen at random within the frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(usize)]
+pub enum FaultKind {
+    /// [`Fault::FlipSentBit`]
+    FlipSentBit,
+    /// [`Fault::FlipReceivedBit`]
+    FlipReceivedBit,
+    /// [`Fault::DropWord`]
+    DropWord,
+    /// [`Fault::ExtraWord`]
+    ExtraWord,
+    /// [`Fault::Truncate`]
+    Truncate,
+    /// [`Fault::DeviceReset`]
+    DeviceReset,
+    /// [`Fault::SpiError`]
+    SpiError,
+}
+
+impl FaultKind {
+    /// Every kind of fault, in the order they are applied to a transfer
+    const ALL: [Self; 7] = [
+        Self::SpiError,
+        Self::DeviceReset,
+        Self::FlipSentBit,
+        Self::DropWord,
+        Self::ExtraWord,
+        Self::Truncate,
+        Self::FlipReceivedBit,
+    ];
+}
+
+/// A [`Transfer`] wrapper which injects faults into the frames passing through it
+///
+/// Transfers are numbered from zero. Scheduled faults are injected in the numbered transfer,
+/// and random faults are injected independently in each transfer with their configured probability
+pub struct FaultInjector<S> {
+    intf: S,
+    reset: Option<fn(&mut S)>,
+    word_len: usize,
+    rates: [f64; FaultKind::ALL.len()],
+    schedule: [Option<(u32, Fault)>; MAX_SCHEDULED_FAULTS],
+    rng: u64,
+    transfers: u32,
+    injected: u32,
+}
+
+impl<S> FaultInjector<S> {
+    /// Wrap `intf`, with no faults configured
+    ///
+    /// Words are assumed to be 24 bits long, the device default
+    pub fn new(intf: S) -> Self {
+        Self {
+            intf,
+            reset: None,
+            word_len: 3,
+            rates: [0.0; FaultKind::ALL.len()],
+            schedule: [None; MAX_SCHEDULED_FAULTS],
+            rng: DEFAULT_SEED,
+            transfers: 0,
+            injected: 0,
+        }
+    }
+
+    /// Use `reset` to reset the device when injecting [`Fault::DeviceReset`]
+    #[must_use]
+    pub fn with_reset(mut self, reset: fn(&mut S)) -> Self {
+        self.reset = Some(reset);
+        self
+    }
+
+    /// Seed the generator choosing random faults
+    #[must_use]
+    pub const fn with_seed(mut self, seed: u64) -> Self {
+        // Xorshift cannot leave the all zero state
+        self.rng = if seed == 0 { DEFAULT_SEED } else { seed };
+        self
+    }
+
+    /// Set the word length used to find word boundaries for [`Fault::DropWord`] and [`Fault::ExtraWord`]
+    ///
+    /// This should be kept in step with the word length of the device
+    pub const fn set_word_length(&mut self, word_length: WordLength) {
+        self.word_len = word_length.byte_count();
+    }
+
+    /// Inject faults of `kind` at random, in each transfer with a probability of `probability`
+    ///
+    /// A probability of zero disables the fault
+    pub const fn set_rate(&mut self, kind: FaultKind, probability: f64) {
+        self.rates[kind as usize] = probability;
+    }
+
+    /// Inject `fault` in the transfer numbered `transfer`
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Error::BufferTooSmall`] if [`MAX_SCHEDULED_FAULTS`] faults are already scheduled
+    pub fn schedule(&mut self, transfer: u32, fault: Fault) -> Result<(), Error> {
+        let slot = self
+            .schedule
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(Error::BufferTooSmall)?;
+        *slot = Some((transfer, fault));
+
+        Ok(())
+    }
+
+    /// Inject `fault` in the next transfer
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Error::BufferTooSmall`] if [`MAX_SCHEDULED_FAULTS`] faults are already scheduled
+    pub fn schedule_next(&mut self, fault: Fault) -> Result<(), Error> {
+        self.schedule(self.transfers, fault)
+    }
+
+    /// Remove every scheduled fault, and disable every random fault
+    pub const fn clear(&mut self) {
+        self.rates = [0.0; FaultKind::ALL.len()];
+        self.schedule = [None; MAX_SCHEDULED_FAULTS];
+    }
+
+    /// The number of transfers made so far
+    pub const fn transfers(&self) -> u32 {
+        self.transfers
+    }
+
+    /// The number of faults injected so far
+    pub const fn injected(&self) -> u32 {
+        self.injected
+    }
+
+    /// Get a reference to the wrapped interface
+    pub const fn inner(&self) -> &S {
+        &self.intf
+    }
+
+    /// Get a mutable reference to the wrapped interface
+    pub const fn inner_mut(&mut self) -> &mut S {
+        &mut self.intf
+    }
+
+    /// Destroy the wrapper and return the wrapped interface
+    pub fn release(self) -> S {
+        self.intf
+    }
+
+    /// Take every fault to inject into the current transfer, in the order they are applied
+    fn take_faults(
+        &mut self,
+        send_len: usize,
+        receive_len: usize,
+    ) -> [Option<Fault>; MAX_SCHEDULED_FAULTS + FaultKind::ALL.len()] {
+        let mut faults = [None; MAX_SCHEDULED_FAULTS + FaultKind::ALL.len()];
+        let mut count = 0;
+
+        for kind in FaultKind::ALL {
+            for slot in &mut self.schedule {
+                if let Some((transfer, fault)) = *slot {
+                    if transfer == self.transfers && fault_kind(fault) == kind {
+                        faults[count] = Some(fault);
+                        count += 1;
+                        *slot = None;
+                    }
+                }
+            }
+
+            let rate = self.rates[kind as usize];
+            if rate > 0.0 && self.next_unit() < rate {
+                faults[count] = Some(self.random_fault(kind, send_len, receive_len));
+                count += 1;
+            }
+        }
+
+        faults
+    }
+
+    /// A fault of `kind` at a random position within the frame
+    const fn random_fault(
+        &mut self,
+        kind: FaultKind,
+        send_len: usize,
+        receive_len: usize,
+    ) -> Fault {
+        match kind {
+            FaultKind::FlipSentBit => Fault::FlipSentBit {
+                bit: self.next_below(send_len * 8),
+            },
+            FaultKind::FlipReceivedBit => Fault::FlipReceivedBit {
+                bit: self.next_below(receive_len * 8),
+            },
+            FaultKind::DropWord => Fault::DropWord {
+                word: self.next_below(receive_len / self.word_len),
+            },
+            FaultKind::ExtraWord => Fault::ExtraWord {
+                word: self.next_below(receive_len / self.word_len),
+            },
+            FaultKind::Truncate => Fault::Truncate {
+                len: self.next_below(receive_len),
+            },
+            FaultKind::DeviceReset => Fault::DeviceReset,
+            FaultKind::SpiError => Fault::SpiError,
+        }
+    }
+
+    /// The next output of the xorshift64* generator
+    const fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+        self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A random number in `[0, 1)`
+    #[allow(clippy::cast_precision_loss)]
+    fn next_unit(&mut self) -> f64 {
+        // The top 53 bits fill the mantissa exactly
+        (self.next_random() >> 11) as f64 / (1_u64 << 53) as f64
+    }
+
+    /// A random number in `[0, bound)`, or zero if `bound` is zero
+    #[allow(clippy::cast_possible_truncation)]
+    const fn next_below(&mut self, bound: usize) -> usize {
+        if bound == 0 {
+            return 0;
+        }
+
+        (self.next_random() % bound as u64) as usize
+    }
+}
+
+impl<S> FaultInjector<S> {
+    /// Make a transfer through the wrapped interface, injecting the faults for this transfer
+    fn inject<W>(&mut self, send: &[u8], receive: &mut [u8]) -> Result<(), Error>
+    where
+        S: Transfer<W>,
+    {
+        let faults = self.take_faults(send.len(), receive.len());
+        self.transfers = self.transfers.wrapping_add(1);
+
+        let mut tx = [0; MAX_WRITE_LEN];
+        let tx = &mut tx[..send.len()];
+        tx.copy_from_slice(send);
+
+        let mut received = false;
+        for fault in faults.into_iter().flatten() {
+            self.injected += 1;
+
+            if !received
+                && !matches!(
+                    fault,
+                    Fault::SpiError | Fault::DeviceReset | Fault::FlipSentBit { .. }
+                )
+            {
+                Transfer::<W>::transfer(&mut self.intf, tx, receive)?;
+                received = true;
+            }
+
+            match fault {
+                Fault::SpiError => return Err(Error::SpiIOError),
+                Fault::DeviceReset => {
+                    if let Some(reset) = self.reset {
+                        reset(&mut self.intf);
+                    }
+                }
+                Fault::FlipSentBit { bit } => flip_bit(tx, bit),
+                Fault::FlipReceivedBit { bit } => flip_bit(receive, bit),
+                Fault::DropWord { word } => {
+                    let start = (word * self.word_len).min(receive.len());
+                    let end = (start + self.word_len).min(receive.len());
+                    receive.copy_within(end.., start);
+                    let len = receive.len();
+                    receive[len - (end - start)..].fill(0);
+                }
+                Fault::ExtraWord { word } => {
+                    let start = (word * self.word_len).min(receive.len());
+                    let end = (start + self.word_len).min(receive.len());
+                    let len = receive.len();
+                    receive.copy_within(start..len - (end - start), end);
+                    receive[start..end].fill(0);
+                }
+                Fault::Truncate { len } => {
+                    let start = len.min(receive.len());
+                    receive[start..].fill(0);
+                }
+            }
+        }
+
+        if !received {
+            Transfer::<W>::transfer(&mut self.intf, tx, receive)?;
+        }
+
+        Ok(())
+    }
+}
+
+// A blanket implementation over the word type would overlap with the one for `WordTransfer`
+impl<S: Transfer<u8>> Transfer<u8> for FaultInjector<S> {
+    fn transfer(&mut self, send: &[u8], receive: &mut [u8]) -> Result<(), Error> {
+        self.inject::<u8>(send, receive)
+    }
+}
+
+impl<S: Transfer<u16>> Transfer<u16> for FaultInjector<S> {
+    fn transfer(&mut self, send: &[u8], receive: &mut [u8]) -> Result<(), Error> {
+        self.inject::<u16>(send, receive)
+    }
+}
+
+/// Invert bit `bit` of `buf`, counting from the MSB of the first byte
+///
+/// Bits past the end of `buf` are ignored
+fn flip_bit(buf: &mut [u8], bit: usize) {
+    if let Some(byte) = buf.get_mut(bit / 8) {
+        *byte ^= 0x80 >> (bit % 8);
+    }
+}
+
+/// The kind of `fault`
+const fn fault_kind(fault: Fault) -> FaultKind {
+    match fault {
+        Fault::FlipSentBit { .. } => FaultKind::FlipSentBit,
+        Fault::FlipReceivedBit { .. } => FaultKind::FlipReceivedBit,
+        Fault::DropWord { .. } => FaultKind::DropWord,
+        Fault::ExtraWord { .. } => FaultKind::ExtraWord,
+        Fault::Truncate { .. } => FaultKind::Truncate,
+        Fault::DeviceReset => FaultKind::DeviceReset,
+        Fault::SpiError => FaultKind::SpiError,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::driver::{Discard, Driver};
+    use crate::interface::Ads131m;
+    use crate::register::{Gain1, Mode, PgaGain};
+    use crate::sim::Simulator;
+
+    /// An interface which receives a counting sequence, and records the frames sent to it
+    #[derive(Default)]
+    struct Counting {
+        sent: Vec<Vec<u8>>,
+    }
+
+    impl Transfer<u8> for Counting {
+        fn transfer(&mut self, send: &[u8], receive: &mut [u8]) -> Result<(), Error> {
+            for (byte, value) in receive.iter_mut().zip(1..) {
+                *byte = value;
+            }
+            self.sent.push(send.to_vec());
+            Ok(())
+        }
+    }
+
+    fn receive(intf: &mut FaultInjector<Counting>, fault: Fault) -> [u8; 12] {
+        let mut rx = [0; 12];
+        intf.schedule_next(fault).unwrap();
+        Transfer::<u8>::transfer(intf, &[0xA5, 0x5A], &mut rx).unwrap();
+        rx
+    }
+
+    fn open(intf: FaultInjector<Simulator<4>>) -> Driver<FaultInjector<Simulator<4>>, u8, 4> {
+        Driver::new(Ads131m::open_ads131m04(intf))
+    }
+
+    #[test]
+    fn frame_faults() {
+        let mut intf = FaultInjector::new(Counting::default());
+
+        assert_eq!(
+            receive(&mut intf, Fault::DropWord { word: 1 }),
+            [1, 2, 3, 7, 8, 9, 10, 11, 12, 0, 0, 0]
+        );
+        assert_eq!(
+            receive(&mut intf, Fault::ExtraWord { word: 1 }),
+            [1, 2, 3, 0, 0, 0, 4, 5, 6, 7, 8, 9]
+        );
+        assert_eq!(
+            receive(&mut intf, Fault::Truncate { len: 4 }),
+            [1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            receive(&mut intf, Fault::FlipReceivedBit { bit: 9 }),
+            [1, 0x42, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+        );
+        assert_eq!(
+            receive(&mut intf, Fault::FlipSentBit { bit: 15 }),
+            [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]
+        );
+
+        intf.set_word_length(WordLength::Bits16);
+        assert_eq!(
+            receive(&mut intf, Fault::DropWord { word: 0 }),
+            [3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 0, 0]
+        );
+
+        assert_eq!(intf.transfers(), 6);
+        assert_eq!(intf.injected(), 6);
+        let sent = intf.release().sent;
+        assert_eq!(sent[3], [0xA5, 0x5A]);
+        assert_eq!(sent[4], [0xA5, 0x5B]);
+    }
+
+    #[test]
+    fn spi_error() {
+        let mut intf = FaultInjector::new(Counting::default());
+        intf.schedule(1, Fault::SpiError).unwrap();
+
+        let mut rx = [0; 2];
+        assert_eq!(
+            Transfer::<u8>::transfer(&mut intf, &[0; 2], &mut rx),
+            Ok(())
+        );
+        assert_eq!(
+            Transfer::<u8>::transfer(&mut intf, &[0; 2], &mut rx),
+            Err(Error::SpiIOError)
+        );
+        assert_eq!(
+            Transfer::<u8>::transfer(&mut intf, &[0; 2], &mut rx),
+            Ok(())
+        );
+
+        // The failed transfer never reached the device
+        assert_eq!(intf.release().sent.len(), 2);
+    }
+
+    #[test]
+    fn schedule_full() {
+        let mut intf = FaultInjector::new(Counting::default());
+        for transfer in 0..MAX_SCHEDULED_FAULTS {
+            intf.schedule(u32::try_from(transfer).unwrap(), Fault::SpiError)
+                .unwrap();
+        }
+        assert_eq!(
+            intf.schedule(0, Fault::SpiError),
+            Err(Error::BufferTooSmall)
+        );
+
+        intf.clear();
+        assert_eq!(intf.schedule(0, Fault::SpiError), Ok(()));
+    }
+
+    #[test]
+    fn receive_crc() {
+        let mut intf = FaultInjector::new(Simulator::new());
+        intf.schedule(0, Fault::FlipReceivedBit { bit: 30 })
+            .unwrap();
+        let mut driver = open(intf);
+
+        assert!(matches!(driver.read_frame(), Err(Error::ReceiveCrc { .. })));
+        assert!(driver.read_frame().unwrap().status.is_some());
+    }
+
+    #[test]
+    fn send_crc() {
+        let mut intf = FaultInjector::new(Simulator::new());
+        // The third transfer writes `GAIN1`, after input CRC checking is enabled
+        intf.schedule(2, Fault::FlipSentBit { bit: 28 }).unwrap();
+        let mut driver = open(intf);
+
+        let mode = Mode {
+            spi_crc_enable: true,
+            reset: false,
+            ..Mode::default()
+        };
+        driver.write_register(mode, &mut Discard).unwrap();
+
+        let gain1 = Gain1 {
+            pga_gain0: PgaGain::Gain2,
+            ..Gain1::default()
+        };
+        assert_eq!(
+            driver.write_register(gain1, &mut Discard),
+            Err(Error::SendCrc)
+        );
+
+        let sim = driver.into_inner().release().release();
+        assert_eq!(sim.registers().gain1, Gain1::default());
+    }
+
+    #[test]
+    fn device_reset() {
+        let mut rx = [0; 18];
+
+        let mut intf =
+            FaultInjector::new(Simulator::<4>::new()).with_reset(Simulator::hardware_reset);
+        intf.schedule(1, Fault::DeviceReset).unwrap();
+        Transfer::<u8>::transfer(&mut intf, &[0; 6], &mut rx).unwrap();
+        Transfer::<u8>::transfer(&mut intf, &[0; 6], &mut rx).unwrap();
+        assert_eq!(rx[..2], [0xFF, 0x24]);
+
+        // Without a hook, a device reset has no effect
+        let mut intf = FaultInjector::new(Simulator::<4>::new());
+        intf.schedule(1, Fault::DeviceReset).unwrap();
+        Transfer::<u8>::transfer(&mut intf, &[0; 6], &mut rx).unwrap();
+        Transfer::<u8>::transfer(&mut intf, &[0; 6], &mut rx).unwrap();
+        assert_eq!(rx[..2], [0x05, 0x0F]);
+    }
+
+    #[test]
+    fn unexpected_response() {
+        let mut intf = FaultInjector::new(Simulator::new()).with_reset(Simulator::hardware_reset);
+        intf.schedule(1, Fault::DeviceReset).unwrap();
+        let mut driver = open(intf);
+
+        // The device acknowledges the spurious reset instead of sending its status
+        assert!(driver.read_frame().is_ok());
+        assert_eq!(driver.read_frame().err(), Some(Error::UnexpectedResponse));
+        assert!(driver.read_frame().is_ok());
+    }
+
+    #[test]
+    fn random_rates() {
+        let run = |seed| {
+            let mut intf = FaultInjector::new(Counting::default()).with_seed(seed);
+            intf.set_rate(FaultKind::SpiError, 0.25);
+            let failures: Vec<bool> = (0..400)
+                .map(|_| Transfer::<u8>::transfer(&mut intf, &[0; 2], &mut [0; 2]).is_err())
+                .collect();
+            assert_eq!(
+                usize::try_from(intf.injected()).unwrap(),
+                failures.iter().filter(|&&failed| failed).count()
+            );
+            failures
+        };
+
+        let failures = run(7);
+        let count = failures.iter().filter(|&&failed| failed).count();
+        assert!((60..140).contains(&count), "{count} failures");
+        assert_eq!(run(7), failures);
+        assert_ne!(run(8), failures);
+    }
+}
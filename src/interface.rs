@@ -1,9 +1,10 @@
 //! Low level message interface for communicating with the device
 
+use core::fmt::{Debug, Formatter};
 use core::marker::PhantomData;
 
 use crate::register::{
-    Address, Channel, ChannelSpecific, CrcType, Global, Mode, Status, WordLength,
+    Address, Channel, ChannelSpecific, Clock, CrcType, Global, Id, Mode, Status, WordLength,
 };
 use crate::spi::Transfer;
 use crate::Error;
@@ -11,17 +12,31 @@ use crate::Error;
 use crc::{Crc, CRC_16_CMS, CRC_16_IBM_3740};
 
 #[cfg(feature = "serde")]
-use serde::{Deserialize, Serialize};
+use serde::de::{Error as _, SeqAccess, Visitor};
+#[cfg(feature = "serde")]
+use serde::ser::SerializeSeq;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 pub use crate::sample_grab::SampleGrab;
 
+/// The maximum number of registers that can be read or written by a single command
+pub const MAX_REGISTER_COUNT: usize = 64;
+
 // Since no const-generic math, we have to use the max possible buffer sizes
-// Max word len * (command + 1 register writes + CRC)
-const MAX_WRITE_LEN: usize = 4 * (1 + 1 + 1);
+/// The maximum length in bytes of a frame sent to the device
+///
+/// Max word len * (command + 64 register writes + CRC)
+pub const MAX_WRITE_LEN: usize = 4 * (1 + MAX_REGISTER_COUNT + 1);
 
-// The max read len is 2 words so that won't be longer than a normal command
-// Max word len * (command + 8 channels + CRC)
-const MAX_READ_LEN: usize = 4 * (1 + 8 + 1);
+// A multi-register read is longer than a normal response of (response + 8 channels + CRC)
+/// The maximum length in bytes of a frame received from the device
+///
+/// Max word len * (response + 64 register reads + CRC)
+pub const MAX_READ_LEN: usize = 4 * (1 + MAX_REGISTER_COUNT + 1);
+
+/// Every channel enabled, as in the `CLOCK` register after a reset
+const ALL_CHANNELS: u8 = 0xFF;
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -32,8 +47,109 @@ enum CommandKind {
     Wakeup,
     Lock,
     Unlock,
-    WriteRegister { addr: Address, data: [u8; 2] },
-    ReadRegister { addr: Address },
+    WriteRegister { addr: Address, data: RegisterWords },
+    ReadRegister { addr: Address, count: u8 },
+}
+
+/// A block of consecutive register words
+#[derive(PartialEq, Eq, Clone, Copy)]
+struct RegisterWords {
+    len: u8,
+    words: [[u8; 2]; MAX_REGISTER_COUNT],
+}
+
+impl RegisterWords {
+    /// Copy a slice of words
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `words` is longer than [`MAX_REGISTER_COUNT`]
+    fn from_slice(words: &[[u8; 2]]) -> Self {
+        let mut block = Self {
+            len: u8::try_from(words.len()).expect("words should be at most MAX_REGISTER_COUNT"),
+            words: [[0; 2]; MAX_REGISTER_COUNT],
+        };
+        block.words[..words.len()].copy_from_slice(words);
+
+        block
+    }
+
+    const fn count(&self) -> u8 {
+        self.len
+    }
+
+    fn as_slice(&self) -> &[[u8; 2]] {
+        &self.words[..usize::from(self.len)]
+    }
+
+    /// Get the word for the register at `target`, if it is within a block starting at `start`
+    fn word_for(&self, start: Address, target: Address) -> Option<[u8; 2]> {
+        let idx = target.address().checked_sub(start.address())?;
+        self.as_slice().get(usize::from(idx)).copied()
+    }
+}
+
+impl Debug for RegisterWords {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.debug_list().entries(self.as_slice()).finish()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for RegisterWords {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut seq = serializer.serialize_seq(Some(usize::from(self.len)))?;
+        for word in self.as_slice() {
+            seq.serialize_element(word)?;
+        }
+
+        seq.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+struct RegisterWordsVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> Visitor<'de> for RegisterWordsVisitor {
+    type Value = RegisterWords;
+
+    fn expecting(&self, formatter: &mut Formatter) -> core::fmt::Result {
+        formatter.write_str("a sequence of at most 64 register words")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut block = RegisterWords::from_slice(&[]);
+        while let Some(word) = seq.next_element()? {
+            if usize::from(block.len) == MAX_REGISTER_COUNT {
+                return Err(A::Error::invalid_length(
+                    MAX_REGISTER_COUNT + 1,
+                    &"a sequence of at most 64 register words",
+                ));
+            }
+
+            block.words[usize::from(block.len)] = word;
+            block.len += 1;
+        }
+
+        Ok(block)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for RegisterWords {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_seq(RegisterWordsVisitor)
+    }
 }
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
@@ -45,7 +161,7 @@ enum ResponseKind {
     Lock,
     Unlock,
     WriteRegister { addr: u8, count: u8 },
-    ReadRegister { addr: Address },
+    ReadRegister { addr: Address, count: u8 },
 }
 
 impl ResponseKind {
@@ -53,7 +169,7 @@ impl ResponseKind {
     /// returning `Status` if the bytes do not match any static response, assuming the command failed
     ///
     /// The static responses are:
-    /// - Reset,
+    /// - Reset, which includes the channel count of the device
     /// - Standby
     /// - Wakeup
     /// - Lock
@@ -63,9 +179,9 @@ impl ResponseKind {
     ///
     /// This will never return `ReadRegister` or `WriteRegister`
     /// as those cannot always be distinguished from other values
-    fn try_decode_static_response(bytes: [u8; 2]) -> Result<Self, Status> {
+    fn try_decode_static_response(bytes: [u8; 2], channel_count: usize) -> Result<Self, Status> {
         match bytes {
-            [0xFF, 0x24] => Ok(Self::Reset),
+            [0xFF, ack] if usize::from(ack) == 0x20 | channel_count => Ok(Self::Reset),
             [0x00, 0x22] => Ok(Self::Standby),
             [0x00, 0x33] => Ok(Self::Wakeup),
             [0x05, 0x55] => Ok(Self::Lock),
@@ -92,6 +208,25 @@ impl ResponseKind {
             count: (bytes[1] & 0x7F) + 1,
         })
     }
+
+    /// Try to decode a multi-register `ReadRegister` response from some bytes,
+    /// returning `Status` if the bytes do not match a valid `ReadRegister`, assuming the command failed
+    ///
+    /// Single register reads do not have an acknowledgement, so they cannot be decoded by this
+    fn try_decode_read_registers(bytes: [u8; 2]) -> Result<Self, Status> {
+        if bytes[0] & 0xE0 != 0xE0 {
+            return Err(Self::decode_null_response(bytes));
+        }
+
+        let Some(addr) = Address::from_address((bytes[0] & 0x1F) << 1 | bytes[1] >> 7) else {
+            return Err(Self::decode_null_response(bytes));
+        };
+
+        Ok(Self::ReadRegister {
+            addr,
+            count: (bytes[1] & 0x7F) + 1,
+        })
+    }
 }
 
 struct ModeCache {
@@ -116,7 +251,7 @@ impl ModeCache {
         }
     }
 
-    fn update_word_length(&mut self, word_length: WordLength) {
+    const fn update_word_length(&mut self, word_length: WordLength) {
         self.word_len = word_length.byte_count();
         self.word_packing = word_length;
     }
@@ -142,8 +277,8 @@ impl Command {
 
     /// Reset the device
     ///
-    /// TODO: After this message is sent, this driver will not perform another transaction
-    /// for 5us while the registers stabilize
+    /// The device needs 5us for the registers to stabilize before the next transaction.
+    /// [`Driver::reset`](crate::driver::Driver::reset) waits for this when it has a delay provider
     ///
     /// After this command is sent the internal mode cache will be updated,
     /// which can change how the driver communicates
@@ -198,7 +333,7 @@ impl Command {
         Self {
             inner: CommandKind::WriteRegister {
                 addr: R::ADDRESS,
-                data: register.to_be_bytes(),
+                data: RegisterWords::from_slice(&[register.to_be_bytes()]),
             },
         }
     }
@@ -209,17 +344,72 @@ impl Command {
         Self {
             inner: CommandKind::WriteRegister {
                 addr: R::address_for_channel(channel),
-                data: register.to_be_bytes(),
+                data: RegisterWords::from_slice(&[register.to_be_bytes()]),
             },
         }
     }
 
+    /// Write to a block of consecutive device registers, starting at `address`
+    ///
+    /// Words are MSB first, and are written to consecutive register addresses.
+    /// Up to [`MAX_REGISTER_COUNT`] registers can be written in a single command
+    ///
+    /// If the block includes the device's [`Mode`] register, the internal mode cache will be updated,
+    /// which can change how the driver communicates
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Error::InvalidRegisterCount`] if `words` is empty,
+    /// or the block extends past the end of the register map
+    pub fn new_write_registers(address: Address, words: &[[u8; 2]]) -> Result<Self, Error> {
+        Self::check_register_range(address, words.len())?;
+
+        Ok(Self {
+            inner: CommandKind::WriteRegister {
+                addr: address,
+                data: RegisterWords::from_slice(words),
+            },
+        })
+    }
+
     /// Read from a device register
     #[must_use]
     pub const fn new_read_register(address: Address) -> Self {
         Self {
-            inner: CommandKind::ReadRegister { addr: address },
+            inner: CommandKind::ReadRegister {
+                addr: address,
+                count: 1,
+            },
+        }
+    }
+
+    /// Read from `count` consecutive device registers, starting at `address`
+    ///
+    /// Up to [`MAX_REGISTER_COUNT`] registers can be read in a single command.
+    /// The result is returned as a [`RegisterBlock`]
+    ///
+    /// # Errors
+    ///
+    /// Will return [`Error::InvalidRegisterCount`] if `count` is zero,
+    /// or the block extends past the end of the register map
+    pub fn new_read_registers(address: Address, count: u8) -> Result<Self, Error> {
+        Self::check_register_range(address, usize::from(count))?;
+
+        Ok(Self {
+            inner: CommandKind::ReadRegister {
+                addr: address,
+                count,
+            },
+        })
+    }
+
+    /// Check that a block of `count` registers starting at `address` fits in the register map
+    fn check_register_range(address: Address, count: usize) -> Result<(), Error> {
+        if count == 0 || usize::from(address.address()) + count > MAX_REGISTER_COUNT {
+            return Err(Error::InvalidRegisterCount);
         }
+
+        Ok(())
     }
 
     /// Check if this is a null command
@@ -270,14 +460,18 @@ impl Command {
         matches!(self.inner, CommandKind::ReadRegister { .. })
     }
 
-    /// Return the channel for this command, if applicable
-    const fn channel(self) -> Option<Channel> {
-        match self.inner {
-            CommandKind::ReadRegister { addr } | CommandKind::WriteRegister { addr, .. } => {
-                addr.channel()
-            }
-            _ => None,
-        }
+    /// Return the highest channel addressed by this command, if applicable
+    fn channel(&self) -> Option<Channel> {
+        let (addr, count) = match &self.inner {
+            CommandKind::ReadRegister { addr, count } => (addr.address(), *count),
+            CommandKind::WriteRegister { addr, data } => (addr.address(), data.count()),
+            _ => return None,
+        };
+
+        (addr..addr + count)
+            .filter_map(Address::from_address)
+            .filter_map(Address::channel)
+            .max_by_key(|channel| u8::from(*channel))
     }
 
     /// Encode this command to `buf` and return the number of bytes written
@@ -289,9 +483,9 @@ impl Command {
     /// # Panics
     ///
     /// Will panic if `buf` does not have room for the command.
-    /// The maximum command length is 2 * `word_len`
+    /// The maximum command length is (1 + [`MAX_REGISTER_COUNT`]) * `word_len`
     #[must_use]
-    fn encode_words(self, buf: &mut [u8], word_len: usize) -> usize {
+    fn encode_words(&self, buf: &mut [u8], word_len: usize) -> usize {
         let bytes = match self.inner {
             CommandKind::Null => [0x00, 0x00],
             CommandKind::Reset => [0x00, 0x11],
@@ -299,13 +493,11 @@ impl Command {
             CommandKind::Wakeup => [0x00, 0x33],
             CommandKind::Lock => [0x05, 0x55],
             CommandKind::Unlock => [0x06, 0x55],
-            CommandKind::WriteRegister { addr, .. } => {
-                let a = addr.address();
-                [0x60 | a >> 1, (a & 0b1) << 7]
+            CommandKind::WriteRegister { addr, data } => {
+                Self::encode_register_opcode(0x60, addr, data.count())
             }
-            CommandKind::ReadRegister { addr } => {
-                let a = addr.address();
-                [0xA0 | a >> 1, (a & 0b1) << 7]
+            CommandKind::ReadRegister { addr, count } => {
+                Self::encode_register_opcode(0xA0, addr, count)
             }
         };
 
@@ -317,22 +509,31 @@ impl Command {
             len += 1;
         }
 
-        if let CommandKind::WriteRegister { data, .. } = self.inner {
-            buf[len..len + 2].copy_from_slice(&data);
-            len += 2;
+        if let CommandKind::WriteRegister { data, .. } = &self.inner {
+            for word in data.as_slice() {
+                let word_end = len + word_len;
+                buf[len..len + 2].copy_from_slice(word);
+                len += 2;
 
-            while len < word_len * 2 {
-                buf[len] = 0;
-                len += 1;
+                while len < word_end {
+                    buf[len] = 0;
+                    len += 1;
+                }
             }
         }
 
         len
     }
 
+    /// Encode the opcode for a register command addressing `count` registers, starting at `addr`
+    const fn encode_register_opcode(opcode: u8, addr: Address, count: u8) -> [u8; 2] {
+        let a = addr.address();
+        [opcode | a >> 1, (a & 0b1) << 7 | (count - 1)]
+    }
+
     /// Get the expected response to the command
     #[must_use]
-    const fn expected_response(self) -> ResponseKind {
+    const fn expected_response(&self) -> ResponseKind {
         match self.inner {
             CommandKind::Null => ResponseKind::Null,
             CommandKind::Reset => ResponseKind::Reset,
@@ -340,11 +541,11 @@ impl Command {
             CommandKind::Wakeup => ResponseKind::Wakeup,
             CommandKind::Lock => ResponseKind::Lock,
             CommandKind::Unlock => ResponseKind::Unlock,
-            CommandKind::WriteRegister { addr, .. } => ResponseKind::WriteRegister {
+            CommandKind::WriteRegister { addr, data } => ResponseKind::WriteRegister {
                 addr: addr.address(),
-                count: 1,
+                count: data.count(),
             },
-            CommandKind::ReadRegister { addr } => ResponseKind::ReadRegister { addr },
+            CommandKind::ReadRegister { addr, count } => ResponseKind::ReadRegister { addr, count },
         }
     }
 }
@@ -359,6 +560,74 @@ pub struct RegisterData {
     pub data: [u8; 2],
 }
 
+/// The result of a multi-register read
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegisterBlock {
+    address: Address,
+    words: RegisterWords,
+}
+
+impl RegisterBlock {
+    /// The address of the first register that was read
+    #[must_use]
+    pub const fn address(&self) -> Address {
+        self.address
+    }
+
+    /// The number of registers that were read
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.words.len as usize
+    }
+
+    /// Check if no registers were read
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.words.len == 0
+    }
+
+    /// Get the data that was read, in address order
+    ///
+    /// Words are MSB first
+    #[must_use]
+    pub fn words(&self) -> &[[u8; 2]] {
+        self.words.as_slice()
+    }
+
+    /// Get the data read from `address`, if it was part of this block
+    #[must_use]
+    pub fn get(&self, address: Address) -> Option<[u8; 2]> {
+        self.words.word_for(self.address, address)
+    }
+
+    /// Iterate over the registers that were read
+    ///
+    /// Addresses in the block which do not have a register are skipped
+    pub fn iter(&self) -> impl Iterator<Item = RegisterData> + '_ {
+        let start = self.address.address();
+        self.words
+            .as_slice()
+            .iter()
+            .zip(start..)
+            .filter_map(|(data, addr)| {
+                Address::from_address(addr).map(|address| RegisterData {
+                    address,
+                    data: *data,
+                })
+            })
+    }
+}
+
+impl From<RegisterData> for RegisterBlock {
+    fn from(read: RegisterData) -> Self {
+        Self {
+            address: read.address,
+            words: RegisterWords::from_slice(&[read.data]),
+        }
+    }
+}
+
 /// A response from the ADC, usually containing a sample grab, a register read result or a a device status message
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -368,59 +637,267 @@ pub struct Response<const CHANNELS: usize> {
     pub sample_grab: Option<SampleGrab<CHANNELS>>,
     /// The data read from a register, if a read was requested
     pub register_read: Option<RegisterData>,
+    /// The data read from multiple registers, if a multi-register read was requested
+    pub register_block: Option<RegisterBlock>,
     /// The current ADC status, if it was returned
     pub status: Option<Status>,
+    /// Metadata decoded from the frame
+    pub frame: FrameInfo,
 }
 
-/// Low level device message interface
+/// Metadata decoded from every frame received from the device
 ///
-/// This handles encoding and decoding SPI frames based on the current device state,
-/// but has no ability to determine when a a SPI transaction should occur.
+/// This allows the device state to be followed continuously, without reading the `STATUS` register
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FrameInfo {
+    /// The raw response word, MSB first
+    pub response: [u8; 2],
+    /// The response word decoded as a status word, if the response was one
+    ///
+    /// This is the same as [`Response::status`]
+    pub status: Option<Status>,
+    /// The channels reported as having data ready in the status word, with channel 0 in the LSB
+    ///
+    /// This is zero if the response was not a status word
+    pub data_ready: u8,
+    /// Whether the `LOCK` bit changed since the previous status word
+    pub lock_changed: bool,
+    /// Whether the `RESYNC` bit changed since the previous status word
+    pub resync_changed: bool,
+    /// The CRC word received at the end of the frame
+    pub crc: u16,
+}
+
+impl FrameInfo {
+    /// Check if the status word reported data ready on `channel`
+    #[must_use]
+    pub const fn is_data_ready(&self, channel: Channel) -> bool {
+        self.data_ready & (1 << channel as u8) != 0
+    }
+}
+
+/// The response expected in a frame, and the changes the frame makes to the device state
+#[derive(Debug, Clone, Copy)]
+struct PendingFrame {
+    response: ResponseKind,
+    read_len: usize,
+    reset: bool,
+    new_mode: Option<Mode>,
+    new_enabled_channels: Option<u8>,
+}
+
+/// The lengths of a prepared SPI frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameLengths {
+    /// The number of bytes to send
+    pub send: usize,
+    /// The number of bytes to receive
+    pub receive: usize,
+}
+
+impl FrameLengths {
+    /// The number of bytes to clock in each direction when the frame is sent and received
+    /// in a single full duplex transfer
+    #[must_use]
+    pub const fn transfer(&self) -> usize {
+        if self.send > self.receive {
+            self.send
+        } else {
+            self.receive
+        }
+    }
+}
+
+/// Communication health counters, kept by the [`FrameCodec`] as frames are decoded
+///
+/// Frames exchanged while probing the communication settings of the device are not counted
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LinkStats {
+    /// Frames received from the device
+    pub frames: u32,
+
+    /// Frames received with an invalid CRC
+    pub receive_crc_errors: u32,
+
+    /// Frames where the device reported a CRC error in the command it received
+    pub send_crc_errors: u32,
+
+    /// Frames with a different response than the command expected
+    pub unexpected_responses: u32,
+
+    /// Frames where the device reported a different word length than expected
+    pub word_length_changes: u32,
+
+    /// Device resets which were not requested
+    ///
+    /// These are detected from an unexpected reset acknowledgement,
+    /// or the reset flag in a status word being set again after it was cleared
+    pub resets: u32,
+
+    /// Frames where the status word reported data ready on an enabled channel
+    /// straight after the previous sample grab was read, so a conversion was missed
+    ///
+    /// The enabled channels are tracked from `CLOCK` register writes, see [`Status::is_overrun`]
+    pub missed_samples: u32,
+}
+
+impl LinkStats {
+    /// Count an error returned while decoding a frame
+    const fn record_error(&mut self, error: &Error) {
+        let counter = match error {
+            Error::ReceiveCrc { .. } => &mut self.receive_crc_errors,
+            Error::SendCrc => &mut self.send_crc_errors,
+            Error::UnexpectedResponse => &mut self.unexpected_responses,
+            Error::WordLengthChanged => &mut self.word_length_changes,
+            _ => return,
+        };
+        *counter = counter.saturating_add(1);
+    }
+}
+
+/// SPI frame encoder and decoder
 ///
+/// This tracks the device state needed to encode commands and decode responses,
+/// independent of how the SPI transaction is performed.
+/// It does not allocate or block, so it can be driven by a DMA engine from an interrupt handler.
 ///
-/// TODO: Description
+/// Each exchange is split into two steps:
+/// 1. [`prepare_frame`](Self::prepare_frame) encodes a command into a TX buffer,
+///    and returns the lengths of the frame to transfer
+/// 2. Once the transfer is complete, [`finish_frame`](Self::finish_frame) checks and decodes
+///    the received frame
 ///
-/// TODO: Examples
-pub struct Ads131m<S: Transfer<W>, W: Copy, const CHANNELS: usize> {
-    intf: S,
-    _word: PhantomData<W>,
-    read_buf: [u8; MAX_READ_LEN],
-    write_buf: [u8; MAX_WRITE_LEN],
+/// ```no_run
+/// # use ads131m::interface::{Command, FrameCodec, MAX_READ_LEN, MAX_WRITE_LEN};
+/// let mut codec = FrameCodec::open_ads131m04();
+/// let mut tx = [0; MAX_WRITE_LEN];
+/// let rx = [0; MAX_READ_LEN];
+///
+/// let len = codec.prepare_frame(&Command::new_null(), &mut tx).unwrap();
+/// // Start a DMA transfer of `tx[..len.transfer()]` into `rx[..len.transfer()]`
+/// // Then, once the transfer completes
+/// let response = codec.finish_frame(&rx[..len.receive]).unwrap();
+/// ```
+pub struct FrameCodec<const CHANNELS: usize> {
     expected_response: ResponseKind,
     mode_cache: ModeCache,
+    pending: Option<PendingFrame>,
+    stats: LinkStats,
+    /// The last status word received
+    last_status: Option<Status>,
+    /// The last known state of the device's reset flag
+    reset_flag: bool,
+    /// Whether the last frame returned a sample grab in response to a null command
+    sampling: bool,
+    /// The channels enabled in the `CLOCK` register, see [`Clock::enabled_channels`]
+    enabled_channels: u8,
 }
 
-impl<S, W, const CHANNELS: usize> Ads131m<S, W, CHANNELS>
-where
-    S: Transfer<W>,
-    W: Copy,
-{
-    /// Communicate with the device
+impl<const CHANNELS: usize> FrameCodec<CHANNELS> {
+    pub(crate) const fn new(mode: Mode) -> Self {
+        Self {
+            expected_response: ResponseKind::Reset,
+            mode_cache: ModeCache::new(mode),
+            pending: None,
+            stats: LinkStats {
+                frames: 0,
+                receive_crc_errors: 0,
+                send_crc_errors: 0,
+                unexpected_responses: 0,
+                word_length_changes: 0,
+                resets: 0,
+                missed_samples: 0,
+            },
+            last_status: None,
+            reset_flag: true,
+            sampling: false,
+            enabled_channels: ALL_CHANNELS,
+        }
+    }
+
+    /// Create a codec for a device which is already running, and last received a null command
+    pub(crate) const fn resume(mode: Mode) -> Self {
+        let mut codec = Self::new(mode);
+        codec.expected_response = ResponseKind::Null;
+        codec.reset_flag = mode.reset;
+        codec
+    }
+
+    /// Convert to a codec for a different channel count, keeping the communication state
+    const fn into_channels<const N: usize>(self) -> FrameCodec<N> {
+        FrameCodec {
+            expected_response: self.expected_response,
+            mode_cache: self.mode_cache,
+            pending: None,
+            stats: self.stats,
+            last_status: self.last_status,
+            reset_flag: self.reset_flag,
+            sampling: false,
+            enabled_channels: self.enabled_channels,
+        }
+    }
+
+    /// Return to the state of a freshly reset device
     ///
-    /// Commands must be frequently exchanged with the ADC to ensure sample grabs do not get dropped
+    /// Use this after the device is reset with the `SYNC/RESET` pin,
+    /// as the device will acknowledge the reset in the next frame.
+    /// The [`LinkStats`] are kept
+    pub fn reset_state(&mut self) {
+        *self = Self {
+            stats: self.stats,
+            ..Self::new(Mode::default())
+        };
+    }
+
+    /// The communication health counters since the codec was created or the counters were cleared
+    #[must_use]
+    pub const fn link_stats(&self) -> LinkStats {
+        self.stats
+    }
+
+    /// Reset every communication health counter to zero
+    pub fn clear_link_stats(&mut self) {
+        self.stats = LinkStats::default();
+    }
+
+    /// Replace the communication health counters, to discard the frames exchanged while probing
+    pub(crate) const fn set_link_stats(&mut self, stats: LinkStats) {
+        self.stats = stats;
+    }
+
+    /// Encode `command` into `tx`, and prepare to decode the response to the previous command
     ///
-    /// # Note
-    /// Responses to commands are given on the following successful exchange,
-    /// ***not*** in the exchange in which they were sent
+    /// Bytes of `tx` after the command, up to [`FrameLengths::transfer`], are zeroed
     ///
     /// # Errors
     ///
-    /// Will return `Err` if communication with the device failed
-    pub fn communicate(&mut self, command: Command) -> Result<Response<CHANNELS>, Error> {
-        let mut write_len = self.encode_command(command)?;
+    /// Will return `Err` if the command addresses a channel the device does not have
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `tx` is shorter than [`MAX_WRITE_LEN`]
+    pub fn prepare_frame(
+        &mut self,
+        command: &Command,
+        tx: &mut [u8],
+    ) -> Result<FrameLengths, Error> {
+        let mut write_len = self.encode_command(command, tx)?;
 
         // Get expected response for upcoming response, and store the new one
         let expected_response =
             core::mem::replace(&mut self.expected_response, command.expected_response());
 
         let read_len = match expected_response {
-            ResponseKind::ReadRegister { .. } => 1 + 1,
+            ResponseKind::ReadRegister { count: 1, .. } => 1 + 1,
+            ResponseKind::ReadRegister { count, .. } => 1 + usize::from(count) + 1,
             _ => 1 + CHANNELS + 1,
         } * self.mode_cache.word_len;
 
         // This should be fine as long as writes are shorter than reads
         if write_len % 2 == 1 {
-            self.write_buf[write_len] = 0;
+            tx[write_len] = 0;
             write_len += 1;
         }
 
@@ -433,62 +910,94 @@ where
             read_len
         };
 
-        self.intf.transfer(
-            &self.write_buf[..write_len],
-            &mut self.read_buf[..real_read_len],
-        )?;
+        let new_mode = match &command.inner {
+            CommandKind::WriteRegister { addr, data } => {
+                data.word_for(*addr, Address::Mode).map(Mode::from_be_bytes)
+            }
+            CommandKind::Reset => Some(Mode::default()),
+            _ => None,
+        };
+        let new_enabled_channels = match &command.inner {
+            CommandKind::WriteRegister { addr, data } => data
+                .word_for(*addr, Address::Clock)
+                .map(|word| Clock::from_be_bytes(word).enabled_channels()),
+            CommandKind::Reset => Some(ALL_CHANNELS),
+            _ => None,
+        };
 
-        let resp = self.decode_response(
+        self.pending = Some(PendingFrame {
+            response: expected_response,
             read_len,
-            expected_response,
-            command.inner == CommandKind::Reset,
-        )?;
-
-        match command.inner {
-            CommandKind::WriteRegister {
-                addr: Address::Mode,
-                data,
-            } => self.mode_cache = ModeCache::new(Mode::from_be_bytes(data)),
-            CommandKind::Reset => self.mode_cache = ModeCache::new(Mode::default()),
-            _ => {}
-        }
+            reset: command.is_reset(),
+            new_mode,
+            new_enabled_channels,
+        });
+
+        let lengths = FrameLengths {
+            send: write_len,
+            receive: real_read_len,
+        };
+        tx[write_len..lengths.transfer()].fill(0);
 
-        Ok(resp)
+        Ok(lengths)
     }
 
-    const fn new(intf: S, mode: Mode) -> Self {
-        Self {
-            intf,
-            _word: PhantomData,
-            read_buf: [0; MAX_READ_LEN],
-            write_buf: [0; MAX_WRITE_LEN],
-            expected_response: ResponseKind::Reset,
-            mode_cache: ModeCache::new(mode),
+    /// Check and decode the frame received after calling [`prepare_frame`](Self::prepare_frame)
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if no frame was prepared, or the received frame was invalid
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `rx` is shorter than [`FrameLengths::receive`]
+    pub fn finish_frame(&mut self, rx: &[u8]) -> Result<Response<CHANNELS>, Error> {
+        let frame = self.pending.take().ok_or(Error::UnexpectedResponse)?;
+        self.stats.frames = self.stats.frames.saturating_add(1);
+
+        let resp = match self.decode_response(rx, frame.read_len, frame.response, frame.reset) {
+            Ok(resp) => resp,
+            Err(e) => {
+                self.stats.record_error(&e);
+                self.sampling = false;
+                return Err(e);
+            }
+        };
+        self.sampling = frame.response == ResponseKind::Null && resp.sample_grab.is_some();
+
+        if frame.reset {
+            self.reset_flag = true;
         }
+        if let Some(mode) = frame.new_mode {
+            self.mode_cache = ModeCache::new(mode);
+            self.reset_flag = mode.reset;
+        }
+        if let Some(enabled_channels) = frame.new_enabled_channels {
+            self.enabled_channels = enabled_channels;
+        }
+
+        Ok(resp)
     }
 
-    fn encode_command(&mut self, command: Command) -> Result<usize, Error> {
+    fn encode_command(&self, command: &Command, buf: &mut [u8]) -> Result<usize, Error> {
         if let Some(channel) = command.channel() {
             if usize::from(u8::from(channel)) > CHANNELS - 1 {
                 return Err(Error::UnsupportedChannel);
             }
         }
 
-        let cmd_len: usize = command.encode_words(&mut self.write_buf, self.mode_cache.word_len);
+        let cmd_len: usize = command.encode_words(buf, self.mode_cache.word_len);
         let mut write_len = cmd_len;
 
         if self.mode_cache.spi_crc_enable {
-            let write_crc = self
-                .mode_cache
-                .crc_table
-                .checksum(&self.write_buf[..cmd_len]);
+            let write_crc = self.mode_cache.crc_table.checksum(&buf[..cmd_len]);
 
-            self.write_buf[write_len..write_len + 2].copy_from_slice(&write_crc.to_be_bytes());
+            buf[write_len..write_len + 2].copy_from_slice(&write_crc.to_be_bytes());
             write_len += 2;
         }
 
         while write_len < (cmd_len + self.mode_cache.word_len) {
-            self.write_buf[write_len] = 0;
+            buf[write_len] = 0;
             write_len += 1;
         }
 
@@ -497,21 +1006,26 @@ where
 
     fn decode_response(
         &mut self,
+        buf: &[u8],
         read_len: usize,
         kind: ResponseKind,
         reset_frame: bool,
     ) -> Result<Response<CHANNELS>, Error> {
+        let crc_idx = read_len - self.mode_cache.word_len;
+
+        // CRCs are always 2 bytes
+        let resp_crc = u16::from_be_bytes(buf[crc_idx..crc_idx + 2].try_into().unwrap());
         if !reset_frame {
-            let crc_idx = read_len - self.mode_cache.word_len;
-
-            // CRCs are always 2 bytes
-            let resp_crc =
-                u16::from_be_bytes(self.read_buf[crc_idx..crc_idx + 2].try_into().unwrap());
-            let computed_crc = self
-                .mode_cache
-                .crc_table
-                .checksum(&self.read_buf[..crc_idx]);
+            let computed_crc = self.mode_cache.crc_table.checksum(&buf[..crc_idx]);
             if resp_crc != computed_crc {
+                // Single register reads do not start with a status word
+                if !matches!(kind, ResponseKind::ReadRegister { count: 1, .. }) {
+                    if let Some(word_length) = self.respaced_word_length(buf, read_len) {
+                        self.mode_cache.update_word_length(word_length);
+                        return Err(Error::WordLengthChanged);
+                    }
+                }
+
                 return Err(Error::ReceiveCrc {
                     computed: computed_crc,
                     received: resp_crc,
@@ -519,34 +1033,45 @@ where
             }
         }
 
-        // TODO: Examine response byte spacing to detect a changing word_len
-
         let mut status = None;
+        let mut previous_status = None;
         let mut register_read = None;
+        let mut register_block = None;
 
-        let resp_bytes = self.read_buf[..2].try_into().unwrap();
+        let resp_bytes = buf[..2].try_into().unwrap();
         let resp = match kind {
-            ResponseKind::ReadRegister { addr } => {
+            ResponseKind::ReadRegister { addr, count: 1 } => {
                 register_read = Some(RegisterData {
                     address: addr,
                     data: resp_bytes,
                 });
                 // Nothing can be checked here
-                Ok(ResponseKind::ReadRegister { addr })
+                Ok(kind)
+            }
+            ResponseKind::ReadRegister { .. } => {
+                ResponseKind::try_decode_read_registers(resp_bytes)
             }
             ResponseKind::WriteRegister { .. } => {
                 ResponseKind::try_decode_write_register(resp_bytes)
             }
-            _ => ResponseKind::try_decode_static_response(resp_bytes),
+            _ => ResponseKind::try_decode_static_response(resp_bytes, CHANNELS),
         };
 
         match resp {
             Ok(k) => {
                 if k != kind {
+                    if k == ResponseKind::Reset {
+                        self.stats.resets = self.stats.resets.saturating_add(1);
+                        self.reset_flag = true;
+                        self.enabled_channels = ALL_CHANNELS;
+                    }
                     return Err(Error::UnexpectedResponse);
                 }
             }
             Err(s) => {
+                self.observe_reset(s.reset);
+                previous_status = self.last_status.replace(s);
+
                 if s.word_length != self.mode_cache.word_packing {
                     // Reset word length
                     self.mode_cache.update_word_length(s.word_length);
@@ -557,25 +1082,87 @@ where
                     return Err(Error::UnexpectedResponse);
                 }
 
+                if self.sampling && s.is_overrun(self.enabled_channels) {
+                    self.stats.missed_samples = self.stats.missed_samples.saturating_add(1);
+                }
                 status = Some(s);
             }
         }
 
-        let sample_grab = if reset_frame {
+        if let ResponseKind::ReadRegister { addr, count } = kind {
+            if count > 1 {
+                register_block = Some(self.decode_register_block(buf, addr, count));
+            }
+        }
+
+        // Register read frames do not contain sample data
+        let sample_grab = if reset_frame || matches!(kind, ResponseKind::ReadRegister { .. }) {
             None
         } else {
             Some(self.decode_samples(
-                &self.read_buf[self.mode_cache.word_len..self.mode_cache.word_len * (CHANNELS + 1)],
+                &buf[self.mode_cache.word_len..self.mode_cache.word_len * (CHANNELS + 1)],
             ))
         };
 
-        Ok(Response {
-            sample_grab,
+        let changed = |bit: fn(&Status) -> bool| matches!((status, previous_status), (Some(s), Some(p)) if bit(&s) != bit(&p));
+        let frame = FrameInfo {
+            response: resp_bytes,
+            status,
+            data_ready: if status.is_some() { resp_bytes[1] } else { 0 },
+            lock_changed: changed(|s| s.lock),
+            resync_changed: changed(|s| s.resync),
+            crc: resp_crc,
+        };
+
+        Ok(Response {
+            sample_grab,
             register_read,
+            register_block,
             status,
+            frame,
         })
     }
 
+    /// Check if a frame which failed its CRC check is spaced for the word length reported in its status word
+    ///
+    /// When the device changes its word length unexpectedly, the CRC is found at a different offset.
+    /// This can only be detected if the frame at the new word length fits in the frame that was read
+    fn respaced_word_length(&self, buf: &[u8], read_len: usize) -> Option<WordLength> {
+        let status = Status::from_be_bytes(buf[..2].try_into().unwrap());
+        if status.word_length == self.mode_cache.word_packing {
+            return None;
+        }
+
+        let words = read_len / self.mode_cache.word_len;
+        let crc_idx = (words - 1) * status.word_length.byte_count();
+        let resp_crc = u16::from_be_bytes(buf.get(crc_idx..crc_idx + 2)?.try_into().unwrap());
+
+        (resp_crc == self.mode_cache.crc_table.checksum(&buf[..crc_idx]))
+            .then_some(status.word_length)
+    }
+
+    /// Track the device's reset flag, counting a reset each time it is set again
+    const fn observe_reset(&mut self, reset: bool) {
+        if reset && !self.reset_flag {
+            self.stats.resets = self.stats.resets.saturating_add(1);
+            self.enabled_channels = ALL_CHANNELS;
+        }
+        self.reset_flag = reset;
+    }
+
+    /// Decode the register words following a multi-register read acknowledgement
+    fn decode_register_block(&self, buf: &[u8], address: Address, count: u8) -> RegisterBlock {
+        let mut words = RegisterWords::from_slice(&[]);
+        words.len = count;
+
+        for (idx, word) in words.words[..usize::from(count)].iter_mut().enumerate() {
+            let word_idx = (idx + 1) * self.mode_cache.word_len;
+            word.copy_from_slice(&buf[word_idx..word_idx + 2]);
+        }
+
+        RegisterBlock { address, words }
+    }
+
     /// Panics if `buf` is not `self.word_len` * `CHANNELS` in length
     fn decode_samples(&self, buf: &[u8]) -> SampleGrab<CHANNELS> {
         debug_assert!(buf.len() == self.mode_cache.word_len * CHANNELS);
@@ -593,13 +1180,363 @@ where
                 WordLength::Bits32Signed => {
                     sample.copy_from_slice(&buf[word_idx + 1..word_idx + 4]);
                 }
-            };
+            }
         }
 
         SampleGrab { data }
     }
 }
 
+/// Low level device message interface
+///
+/// This handles encoding and decoding SPI frames based on the current device state,
+/// but has no ability to determine when a a SPI transaction should occur.
+///
+///
+/// TODO: Description
+///
+/// TODO: Examples
+pub struct Ads131m<S: Transfer<W>, W: Copy, const CHANNELS: usize> {
+    intf: S,
+    _word: PhantomData<W>,
+    read_buf: [u8; MAX_READ_LEN],
+    write_buf: [u8; MAX_WRITE_LEN],
+    codec: FrameCodec<CHANNELS>,
+}
+
+impl<S, W, const CHANNELS: usize> Ads131m<S, W, CHANNELS>
+where
+    S: Transfer<W>,
+    W: Copy,
+{
+    /// Communicate with the device
+    ///
+    /// Commands must be frequently exchanged with the ADC to ensure sample grabs do not get dropped
+    ///
+    /// # Note
+    /// Responses to commands are given on the following successful exchange,
+    /// ***not*** in the exchange in which they were sent
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed
+    pub fn communicate(&mut self, command: Command) -> Result<Response<CHANNELS>, Error> {
+        let len = self.codec.prepare_frame(&command, &mut self.write_buf)?;
+
+        self.intf.transfer(
+            &self.write_buf[..len.send],
+            &mut self.read_buf[..len.receive],
+        )?;
+
+        self.codec.finish_frame(&self.read_buf[..len.receive])
+    }
+
+    /// Destroy the driver instance and return the SPI interface
+    pub fn release(self) -> S {
+        self.intf
+    }
+
+    /// Return to the communication settings of a freshly reset device
+    ///
+    /// Use this after the device is reset with the `SYNC/RESET` pin,
+    /// as the device will acknowledge the reset in the next exchange
+    pub fn reset_state(&mut self) {
+        self.codec.reset_state();
+    }
+
+    /// The communication health counters since the interface was opened or the counters were cleared
+    #[must_use]
+    pub const fn link_stats(&self) -> LinkStats {
+        self.codec.link_stats()
+    }
+
+    /// Reset every communication health counter to zero
+    pub fn clear_link_stats(&mut self) {
+        self.codec.clear_link_stats();
+    }
+
+    const fn new(intf: S, mode: Mode) -> Self {
+        Self {
+            intf,
+            _word: PhantomData,
+            read_buf: [0; MAX_READ_LEN],
+            write_buf: [0; MAX_WRITE_LEN],
+            codec: FrameCodec::new(mode),
+        }
+    }
+
+    /// Convert to an interface for a different channel count, keeping the communication state
+    fn into_channels<const N: usize>(self) -> Ads131m<S, W, N> {
+        Ads131m {
+            intf: self.intf,
+            _word: PhantomData,
+            read_buf: [0; MAX_READ_LEN],
+            write_buf: [0; MAX_WRITE_LEN],
+            codec: self.codec.into_channels(),
+        }
+    }
+
+    /// Probe every word length and CRC type until the device responds consistently
+    fn detect(intf: S) -> Result<Self, Error> {
+        let mut adc = Self::new(intf, Mode::default());
+        adc.detect_mode()?;
+
+        Ok(adc)
+    }
+
+    /// Probe every word length and CRC type until the device responds consistently,
+    /// and switch to the communication settings found
+    ///
+    /// Returns the `MODE` register read from the device
+    pub(crate) fn detect_mode(&mut self) -> Result<Mode, Error> {
+        let stats = self.codec.link_stats();
+        let result = self.probe_all();
+        self.codec.set_link_stats(stats);
+
+        result
+    }
+
+    /// Probe every word length and CRC type, stopping at the first one the device responds to
+    fn probe_all(&mut self) -> Result<Mode, Error> {
+        let mut last_err = Error::UnexpectedResponse;
+
+        for word_length in [
+            WordLength::Bits24,
+            WordLength::Bits16,
+            WordLength::Bits32Zero,
+            WordLength::Bits32Signed,
+        ] {
+            for crc_type in [CrcType::Ccitt, CrcType::Ansi] {
+                // Always send CRCs, which the device ignores if SPI CRC checking is disabled
+                let probe_mode = Mode {
+                    word_length,
+                    crc_type,
+                    spi_crc_enable: true,
+                    ..Mode::default()
+                };
+
+                match self.probe(probe_mode) {
+                    Ok(mode) => {
+                        self.codec = FrameCodec::resume(mode);
+                        return Ok(mode);
+                    }
+                    Err(Error::SpiIOError) => return Err(Error::SpiIOError),
+                    Err(e) => last_err = e,
+                }
+            }
+        }
+
+        Err(last_err)
+    }
+
+    /// Check if the device communicates using `probe_mode`, and read the `MODE` register if it does
+    ///
+    /// Response CRCs are only valid if both the word spacing and the CRC type are correct,
+    /// and the `STATUS` word reports the word length in use
+    fn probe(&mut self, probe_mode: Mode) -> Result<Mode, Error> {
+        self.codec = FrameCodec::resume(probe_mode);
+
+        // The first response depends on whatever was sent before the probe,
+        // and may have changed the mode cache while being decoded
+        let _ = self.communicate(Command::new_null());
+        self.codec = FrameCodec::resume(probe_mode);
+        let _ = self.communicate(Command::new_read_register(Address::Mode))?;
+        let mode = self
+            .communicate(Command::new_null())?
+            .register_read
+            .map(|reg| Mode::from_be_bytes(reg.data))
+            .ok_or(Error::UnexpectedResponse)?;
+
+        if mode.word_length != probe_mode.word_length || mode.crc_type != probe_mode.crc_type {
+            return Err(Error::UnexpectedResponse);
+        }
+
+        Ok(mode)
+    }
+}
+
+/// A device message interface for any ADS131M model
+///
+/// This allows a single firmware image to support boards populated with different models,
+/// with the model chosen at runtime from the `ID` register
+pub enum AnyAds131m<S: Transfer<W>, W: Copy> {
+    /// An `ADS131M02` device
+    Ads131m02(Ads131m<S, W, 2>),
+    /// An `ADS131M03` device
+    Ads131m03(Ads131m<S, W, 3>),
+    /// An `ADS131M04` device
+    Ads131m04(Ads131m<S, W, 4>),
+    /// An `ADS131M06` device
+    Ads131m06(Ads131m<S, W, 6>),
+    /// An `ADS131M08` device
+    Ads131m08(Ads131m<S, W, 8>),
+}
+
+impl<S, W> AnyAds131m<S, W>
+where
+    S: Transfer<W>,
+    W: Copy,
+{
+    /// Initialize an ADC driver for whichever model the device reports in its `ID` register
+    ///
+    /// The SPI interface must be configured for SPI mode 1 and the device must
+    /// have it's `MODE` register in the default (reset) state in order for
+    /// communications to work properly
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device reported an unknown channel count
+    pub fn open(intf: S) -> Result<Self, Error> {
+        Self::open_with_mode(intf, Mode::default())
+    }
+
+    /// Initialize an ADC driver for whichever model the device reports in its `ID` register,
+    /// with a custom initial state
+    ///
+    /// This does not configure the `MODE` register, instead this allows
+    /// communication with a device already in a non-default communication configuration
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device reported an unknown channel count
+    pub fn open_with_mode(intf: S, mode: Mode) -> Result<Self, Error> {
+        let mut adc = Ads131m::<S, W, 2>::new(intf, mode);
+
+        // The first response is sized for the populated model, so it cannot be decoded yet,
+        // and may have changed the mode cache and link stats while being decoded
+        let stats = adc.codec.link_stats();
+        let _ = adc.communicate(Command::new_read_register(Address::Id));
+        adc.codec.mode_cache = ModeCache::new(mode);
+        adc.codec.set_link_stats(stats);
+
+        // Single register read responses are the same length for every model
+        let id = adc
+            .communicate(Command::new_null())?
+            .register_read
+            .map(|reg| Id::from_be_bytes(reg.data))
+            .ok_or(Error::UnexpectedResponse)?;
+
+        Ok(match id.channel_count {
+            2 => Self::Ads131m02(adc),
+            3 => Self::Ads131m03(adc.into_channels()),
+            4 => Self::Ads131m04(adc.into_channels()),
+            6 => Self::Ads131m06(adc.into_channels()),
+            8 => Self::Ads131m08(adc.into_channels()),
+            _ => return Err(Error::UnexpectedResponse),
+        })
+    }
+
+    /// The number of channels of the device
+    #[must_use]
+    pub const fn channel_count(&self) -> usize {
+        match self {
+            Self::Ads131m02(_) => 2,
+            Self::Ads131m03(_) => 3,
+            Self::Ads131m04(_) => 4,
+            Self::Ads131m06(_) => 6,
+            Self::Ads131m08(_) => 8,
+        }
+    }
+
+    /// Destroy the driver instance and return the SPI interface
+    pub fn release(self) -> S {
+        match self {
+            Self::Ads131m02(adc) => adc.release(),
+            Self::Ads131m03(adc) => adc.release(),
+            Self::Ads131m04(adc) => adc.release(),
+            Self::Ads131m06(adc) => adc.release(),
+            Self::Ads131m08(adc) => adc.release(),
+        }
+    }
+}
+
+impl FrameCodec<2> {
+    /// Initialize an `ADS131M02` frame codec
+    ///
+    /// The device must have it's `MODE` register in the default (reset) state
+    #[must_use]
+    pub fn open_ads131m02() -> Self {
+        Self::new(Mode::default())
+    }
+
+    /// Initialize an `ADS131M02` frame codec for a device already in a non-default
+    /// communication configuration
+    #[must_use]
+    pub const fn open_ads131m02_with_mode(mode: Mode) -> Self {
+        Self::new(mode)
+    }
+}
+
+impl FrameCodec<3> {
+    /// Initialize an `ADS131M03` frame codec
+    ///
+    /// The device must have it's `MODE` register in the default (reset) state
+    #[must_use]
+    pub fn open_ads131m03() -> Self {
+        Self::new(Mode::default())
+    }
+
+    /// Initialize an `ADS131M03` frame codec for a device already in a non-default
+    /// communication configuration
+    #[must_use]
+    pub const fn open_ads131m03_with_mode(mode: Mode) -> Self {
+        Self::new(mode)
+    }
+}
+
+impl FrameCodec<4> {
+    /// Initialize an `ADS131M04` frame codec
+    ///
+    /// The device must have it's `MODE` register in the default (reset) state
+    #[must_use]
+    pub fn open_ads131m04() -> Self {
+        Self::new(Mode::default())
+    }
+
+    /// Initialize an `ADS131M04` frame codec for a device already in a non-default
+    /// communication configuration
+    #[must_use]
+    pub const fn open_ads131m04_with_mode(mode: Mode) -> Self {
+        Self::new(mode)
+    }
+}
+
+impl FrameCodec<6> {
+    /// Initialize an `ADS131M06` frame codec
+    ///
+    /// The device must have it's `MODE` register in the default (reset) state
+    #[must_use]
+    pub fn open_ads131m06() -> Self {
+        Self::new(Mode::default())
+    }
+
+    /// Initialize an `ADS131M06` frame codec for a device already in a non-default
+    /// communication configuration
+    #[must_use]
+    pub const fn open_ads131m06_with_mode(mode: Mode) -> Self {
+        Self::new(mode)
+    }
+}
+
+impl FrameCodec<8> {
+    /// Initialize an `ADS131M08` frame codec
+    ///
+    /// The device must have it's `MODE` register in the default (reset) state
+    #[must_use]
+    pub fn open_ads131m08() -> Self {
+        Self::new(Mode::default())
+    }
+
+    /// Initialize an `ADS131M08` frame codec for a device already in a non-default
+    /// communication configuration
+    #[must_use]
+    pub const fn open_ads131m08_with_mode(mode: Mode) -> Self {
+        Self::new(mode)
+    }
+}
+
 impl<S, W> Ads131m<S, W, 2>
 where
     S: Transfer<W>,
@@ -624,6 +1561,23 @@ where
     pub const fn open_ads131m02_with_mode(intf: S, mode: Mode) -> Self {
         Self::new(intf, mode)
     }
+
+    /// Initialize an `ADS131M02` ADC driver for a device in an unknown communication configuration
+    ///
+    /// The word length and CRC type are detected by probing the device,
+    /// then the `MODE` register is read to configure the driver.
+    /// This allows reattaching to a device which kept running while the MCU restarted
+    ///
+    /// The SPI interface must be configured for SPI mode 1 in order for
+    /// communications to work properly
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not respond consistently in any configuration
+    pub fn detect_ads131m02(intf: S) -> Result<Self, Error> {
+        Self::detect(intf)
+    }
 }
 
 impl<S, W> Ads131m<S, W, 3>
@@ -650,6 +1604,23 @@ where
     pub const fn open_ads131m03_with_mode(intf: S, mode: Mode) -> Self {
         Self::new(intf, mode)
     }
+
+    /// Initialize an `ADS131M03` ADC driver for a device in an unknown communication configuration
+    ///
+    /// The word length and CRC type are detected by probing the device,
+    /// then the `MODE` register is read to configure the driver.
+    /// This allows reattaching to a device which kept running while the MCU restarted
+    ///
+    /// The SPI interface must be configured for SPI mode 1 in order for
+    /// communications to work properly
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not respond consistently in any configuration
+    pub fn detect_ads131m03(intf: S) -> Result<Self, Error> {
+        Self::detect(intf)
+    }
 }
 
 impl<S, W> Ads131m<S, W, 4>
@@ -676,6 +1647,23 @@ where
     pub const fn open_ads131m04_with_mode(intf: S, mode: Mode) -> Self {
         Self::new(intf, mode)
     }
+
+    /// Initialize an `ADS131M04` ADC driver for a device in an unknown communication configuration
+    ///
+    /// The word length and CRC type are detected by probing the device,
+    /// then the `MODE` register is read to configure the driver.
+    /// This allows reattaching to a device which kept running while the MCU restarted
+    ///
+    /// The SPI interface must be configured for SPI mode 1 in order for
+    /// communications to work properly
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not respond consistently in any configuration
+    pub fn detect_ads131m04(intf: S) -> Result<Self, Error> {
+        Self::detect(intf)
+    }
 }
 
 impl<S, W> Ads131m<S, W, 6>
@@ -702,6 +1690,23 @@ where
     pub const fn open_ads131m06_with_mode(intf: S, mode: Mode) -> Self {
         Self::new(intf, mode)
     }
+
+    /// Initialize an `ADS131M06` ADC driver for a device in an unknown communication configuration
+    ///
+    /// The word length and CRC type are detected by probing the device,
+    /// then the `MODE` register is read to configure the driver.
+    /// This allows reattaching to a device which kept running while the MCU restarted
+    ///
+    /// The SPI interface must be configured for SPI mode 1 in order for
+    /// communications to work properly
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not respond consistently in any configuration
+    pub fn detect_ads131m06(intf: S) -> Result<Self, Error> {
+        Self::detect(intf)
+    }
 }
 
 impl<S, W> Ads131m<S, W, 8>
@@ -728,12 +1733,34 @@ where
     pub const fn open_ads131m08_with_mode(intf: S, mode: Mode) -> Self {
         Self::new(intf, mode)
     }
+
+    /// Initialize an `ADS131M08` ADC driver for a device in an unknown communication configuration
+    ///
+    /// The word length and CRC type are detected by probing the device,
+    /// then the `MODE` register is read to configure the driver.
+    /// This allows reattaching to a device which kept running while the MCU restarted
+    ///
+    /// The SPI interface must be configured for SPI mode 1 in order for
+    /// communications to work properly
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed,
+    /// or the device did not respond consistently in any configuration
+    pub fn detect_ads131m08(intf: S) -> Result<Self, Error> {
+        Self::detect(intf)
+    }
 }
 
 #[cfg(test)]
 #[allow(clippy::too_many_lines)]
 mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
     use super::*;
+    use crate::spi::replay::ReplaySpi;
 
     #[test]
     fn message_encode() {
@@ -794,30 +1821,150 @@ mod tests {
         }
     }
 
+    #[test]
+    fn register_message_encode() {
+        for (message, word_len, bytes, expected_len) in [
+            (
+                Command::new_read_register(Address::Clock),
+                3,
+                [0b1010_0001, 0b1000_0000, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                3,
+            ),
+            (
+                Command::new_read_registers(Address::ChannelConfig(Channel::Zero), 5).unwrap(),
+                2,
+                [0b1010_0100, 0b1000_0100, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                2,
+            ),
+            (
+                Command::new_write_global_register(Mode::default()),
+                3,
+                [0b0110_0001, 0b0000_0000, 0, 0x05, 0x10, 0, 0, 0, 0, 0, 0, 0],
+                6,
+            ),
+            (
+                Command::new_write_registers(Address::Gain1, &[[0x12, 0x34], [0x56, 0x78]])
+                    .unwrap(),
+                3,
+                [
+                    0b0110_0010,
+                    0b0000_0001,
+                    0,
+                    0x12,
+                    0x34,
+                    0,
+                    0x56,
+                    0x78,
+                    0,
+                    0,
+                    0,
+                    0,
+                ],
+                9,
+            ),
+            (
+                Command::new_write_registers(Address::Gain1, &[[0x12, 0x34], [0x56, 0x78]])
+                    .unwrap(),
+                4,
+                [
+                    0b0110_0010,
+                    0b0000_0001,
+                    0,
+                    0,
+                    0x12,
+                    0x34,
+                    0,
+                    0,
+                    0x56,
+                    0x78,
+                    0,
+                    0,
+                ],
+                12,
+            ),
+        ] {
+            let mut buf = [0u8; 12];
+            let len = message.encode_words(&mut buf, word_len);
+            assert_eq!(len, expected_len);
+            assert_eq!(buf, bytes);
+        }
+    }
+
+    #[test]
+    fn register_range_check() {
+        assert_eq!(
+            Command::new_read_registers(Address::Id, 0),
+            Err(Error::InvalidRegisterCount)
+        );
+        assert_eq!(
+            Command::new_write_registers(Address::Mode, &[]),
+            Err(Error::InvalidRegisterCount)
+        );
+        assert_eq!(
+            Command::new_read_registers(Address::RegisterMapCrc, 3),
+            Err(Error::InvalidRegisterCount)
+        );
+        assert_eq!(
+            Command::new_write_registers(Address::Mode, &[[0; 2]; MAX_REGISTER_COUNT - 1]),
+            Err(Error::InvalidRegisterCount)
+        );
+        assert!(Command::new_read_registers(Address::RegisterMapCrc, 2).is_ok());
+        assert!(Command::new_read_registers(Address::Id, 64).is_ok());
+        assert!(Command::new_write_registers(Address::Mode, &[[0; 2]; 62]).is_ok());
+    }
+
+    #[test]
+    fn register_command_channel() {
+        assert_eq!(Command::new_read_register(Address::Clock).channel(), None);
+        assert_eq!(
+            Command::new_read_registers(Address::ChannelConfig(Channel::One), 5)
+                .unwrap()
+                .channel(),
+            Some(Channel::One)
+        );
+        assert_eq!(
+            Command::new_read_registers(Address::Mode, 15)
+                .unwrap()
+                .channel(),
+            Some(Channel::One)
+        );
+        assert_eq!(
+            Command::new_read_registers(Address::ChannelGainCalLsb(Channel::Seven), 16)
+                .unwrap()
+                .channel(),
+            Some(Channel::Seven)
+        );
+    }
+
     #[test]
     fn response_decode() {
         assert_eq!(
-            ResponseKind::try_decode_static_response([0b1111_1111, 0b0010_0100]),
+            ResponseKind::try_decode_static_response([0b1111_1111, 0b0010_0100], 4),
+            Ok(ResponseKind::Reset)
+        );
+        assert_eq!(
+            ResponseKind::try_decode_static_response([0b1111_1111, 0b0010_1000], 8),
             Ok(ResponseKind::Reset)
         );
+        assert!(ResponseKind::try_decode_static_response([0b1111_1111, 0b0010_1000], 4).is_err());
         assert_eq!(
-            ResponseKind::try_decode_static_response([0b0000_0000, 0b0010_0010]),
+            ResponseKind::try_decode_static_response([0b0000_0000, 0b0010_0010], 4),
             Ok(ResponseKind::Standby)
         );
         assert_eq!(
-            ResponseKind::try_decode_static_response([0b0000_0000, 0b0011_0011]),
+            ResponseKind::try_decode_static_response([0b0000_0000, 0b0011_0011], 4),
             Ok(ResponseKind::Wakeup)
         );
         assert_eq!(
-            ResponseKind::try_decode_static_response([0b0000_0101, 0b0101_0101]),
+            ResponseKind::try_decode_static_response([0b0000_0101, 0b0101_0101], 4),
             Ok(ResponseKind::Lock)
         );
         assert_eq!(
-            ResponseKind::try_decode_static_response([0b0000_0110, 0b0101_0101]),
+            ResponseKind::try_decode_static_response([0b0000_0110, 0b0101_0101], 4),
             Ok(ResponseKind::Unlock)
         );
         assert_eq!(
-            ResponseKind::try_decode_static_response([0b0000_0101, 0b0000_0000]),
+            ResponseKind::try_decode_static_response([0b0000_0101, 0b0000_0000], 4),
             Err(Status {
                 lock: false,
                 resync: false,
@@ -837,7 +1984,7 @@ mod tests {
             })
         );
         assert_eq!(
-            ResponseKind::try_decode_static_response([0b1111_1111, 0b1111_1111]),
+            ResponseKind::try_decode_static_response([0b1111_1111, 0b1111_1111], 4),
             Err(Status {
                 lock: true,
                 resync: true,
@@ -888,7 +2035,18 @@ mod tests {
             })
         );
         assert_eq!(
-            ResponseKind::try_decode_static_response([0b1111_1111, 0b0001_1111]),
+            ResponseKind::try_decode_read_registers([0b1110_0100, 0b1000_0100]),
+            Ok(ResponseKind::ReadRegister {
+                addr: Address::ChannelConfig(Channel::Zero),
+                count: 5
+            })
+        );
+        assert_eq!(
+            ResponseKind::try_decode_read_registers([0b0000_0101, 0b0000_0000]),
+            Err(Status::default())
+        );
+        assert_eq!(
+            ResponseKind::try_decode_static_response([0b1111_1111, 0b0001_1111], 4),
             Err(Status {
                 lock: true,
                 resync: true,
@@ -908,7 +2066,7 @@ mod tests {
             })
         );
         assert_eq!(
-            ResponseKind::try_decode_static_response([0b0000_0000, 0b0010_0000]),
+            ResponseKind::try_decode_static_response([0b0000_0000, 0b0010_0000], 4),
             Err(Status {
                 lock: false,
                 resync: false,
@@ -928,4 +2086,374 @@ mod tests {
             })
         );
     }
+
+    #[test]
+    fn read_register_block() {
+        let intf = ReplaySpi::new(
+            3,
+            &[
+                &[0xFF, 0x24, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                &[
+                    0xE1, 0x84, 0, 0xFF, 0x0E, 0, 0x00, 0x00, 0, 0x00, 0x00, 0, 0x06, 0x00, 0,
+                    0x00, 0x00, 0,
+                ],
+            ],
+        );
+        let mut adc = Ads131m::open_ads131m04(intf);
+
+        let resp = adc
+            .communicate(Command::new_read_registers(Address::Clock, 5).unwrap())
+            .unwrap();
+        assert!(resp.register_block.is_none());
+
+        let resp = adc.communicate(Command::new_null()).unwrap();
+        assert!(resp.sample_grab.is_none());
+        assert!(resp.register_read.is_none());
+
+        let block = resp.register_block.unwrap();
+        assert_eq!(block.address(), Address::Clock);
+        assert_eq!(block.len(), 5);
+        assert_eq!(block.get(Address::Config), Some([0x06, 0x00]));
+        assert_eq!(block.get(Address::Mode), None);
+        assert_eq!(block.get(Address::ChannelConfig(Channel::Zero)), None);
+        assert_eq!(
+            block.iter().map(|r| r.address).collect::<Vec<_>>(),
+            [
+                Address::Clock,
+                Address::Gain1,
+                Address::Gain2,
+                Address::Config,
+                Address::ThresholdMsb
+            ]
+        );
+        assert_eq!(adc.intf.sent[0][..3], [0xA1, 0x84, 0x00]);
+    }
+
+    #[test]
+    fn write_register_block_updates_mode() {
+        let mode = Mode {
+            word_length: WordLength::Bits16,
+            ..Mode::default()
+        };
+        let intf = ReplaySpi::new(
+            3,
+            &[
+                &[0xFF, 0x24, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                &[0x41, 0x01, 0, 0, 0, 0, 0, 0, 0, 0],
+            ],
+        );
+        let mut adc = Ads131m::open_ads131m04(intf);
+
+        let cmd = Command::new_write_registers(Address::Mode, &[mode.to_be_bytes(), [0xFF, 0x0E]])
+            .unwrap();
+        let _ = adc.communicate(cmd).unwrap();
+        assert_eq!(adc.codec.mode_cache.word_packing, WordLength::Bits16);
+
+        let resp = adc.communicate(Command::new_null()).unwrap();
+        assert!(resp.sample_grab.is_some());
+        assert_eq!(
+            adc.intf.sent[0][..9],
+            [0x61, 0x01, 0x00, 0x04, 0x10, 0x00, 0xFF, 0x0E, 0x00]
+        );
+    }
+
+    #[test]
+    fn frame_codec() {
+        let crc = Crc::<u16>::new(&CRC_16_IBM_3740);
+        let mut codec = FrameCodec::open_ads131m04();
+        let mut tx = [0xAA; MAX_WRITE_LEN];
+
+        assert!(matches!(
+            codec.finish_frame(&[0; 18]),
+            Err(Error::UnexpectedResponse)
+        ));
+
+        let len = codec.prepare_frame(&Command::new_null(), &mut tx).unwrap();
+        assert_eq!(
+            len,
+            FrameLengths {
+                send: 6,
+                receive: 18
+            }
+        );
+        assert_eq!(len.transfer(), 18);
+        assert_eq!(tx[..18], [0; 18]);
+
+        let mut rx = [0; 18];
+        rx[..2].copy_from_slice(&[0xFF, 0x24]);
+        let checksum = crc.checksum(&rx[..15]);
+        rx[15..17].copy_from_slice(&checksum.to_be_bytes());
+        let _ = codec.finish_frame(&rx).unwrap();
+
+        let len = codec
+            .prepare_frame(
+                &Command::new_write_registers(Address::Clock, &[[0; 2]; 10]).unwrap(),
+                &mut tx,
+            )
+            .unwrap();
+        assert_eq!(len.send, 36);
+        assert_eq!(len.receive, 18);
+        assert_eq!(len.transfer(), 36);
+
+        let mut rx = [0; 18];
+        rx[..6].copy_from_slice(&[0x05, 0x00, 0x00, 0x12, 0x34, 0x56]);
+        let checksum = crc.checksum(&rx[..15]);
+        rx[15..17].copy_from_slice(&checksum.to_be_bytes());
+        let resp = codec.finish_frame(&rx).unwrap();
+        assert_eq!(resp.sample_grab.unwrap().into_i32_array()[0], 0x12_3456);
+
+        let _ = codec.prepare_frame(&Command::new_null(), &mut tx).unwrap();
+        rx[15..17].copy_from_slice(&[0, 0]);
+        assert!(matches!(
+            codec.finish_frame(&rx),
+            Err(Error::ReceiveCrc { .. })
+        ));
+        assert_eq!(codec.link_stats().frames, 3);
+        assert_eq!(codec.link_stats().receive_crc_errors, 1);
+    }
+
+    #[test]
+    fn missed_samples_enabled_channels() {
+        let status = |drdy: u8| [0x05, drdy, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let intf = ReplaySpi::new(
+            3,
+            &[
+                &[0xFF, 0x24, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                &[0x41, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                &status(0x00),
+                &status(0x02),
+                &status(0x01),
+            ],
+        );
+        let mut adc = Ads131m::open_ads131m04(intf);
+
+        let clock = Clock {
+            channel1_en: false,
+            ..Clock::default()
+        };
+        let cmd = Command::new_write_registers(Address::Clock, &[clock.to_be_bytes()]).unwrap();
+        let _ = adc.communicate(cmd).unwrap();
+        for _ in 0..4 {
+            let _ = adc.communicate(Command::new_null()).unwrap();
+        }
+
+        // Channel 1 is disabled, so only the late channel 0 conversion was missed
+        assert_eq!(adc.link_stats().missed_samples, 1);
+    }
+
+    #[test]
+    fn word_length_spacing() {
+        let crc = Crc::<u16>::new(&CRC_16_IBM_3740);
+        let mut codec = FrameCodec::open_ads131m04();
+        let mut tx = [0; MAX_WRITE_LEN];
+
+        let _ = codec.prepare_frame(&Command::new_null(), &mut tx).unwrap();
+        let mut rx = [0; 18];
+        rx[..2].copy_from_slice(&[0xFF, 0x24]);
+        let checksum = crc.checksum(&rx[..15]);
+        rx[15..17].copy_from_slice(&checksum.to_be_bytes());
+        let _ = codec.finish_frame(&rx).unwrap();
+
+        // The device reports 16-bit words, and the CRC follows 5 16-bit words
+        let _ = codec.prepare_frame(&Command::new_null(), &mut tx).unwrap();
+        let mut rx = [0; 18];
+        rx[..4].copy_from_slice(&[0x04, 0x00, 0x12, 0x34]);
+        let checksum = crc.checksum(&rx[..10]);
+        rx[10..12].copy_from_slice(&checksum.to_be_bytes());
+        assert_eq!(
+            codec.finish_frame(&rx).err(),
+            Some(Error::WordLengthChanged)
+        );
+        assert_eq!(codec.mode_cache.word_len, 2);
+
+        let len = codec.prepare_frame(&Command::new_null(), &mut tx).unwrap();
+        assert_eq!(len.receive, 12);
+        let resp = codec.finish_frame(&rx[..12]).unwrap();
+        assert_eq!(resp.sample_grab.unwrap().into_i32_array()[0], 0x12_3400);
+    }
+
+    #[test]
+    fn link_stats() {
+        let frame = |status: [u8; 2]| {
+            let mut frame = [0; 15];
+            frame[..2].copy_from_slice(&status);
+            frame
+        };
+        let frames = [
+            frame([0xFF, 0x24]),
+            frame([0x05, 0x00]),
+            // Data was ready again straight after the previous sample grab
+            frame([0x05, 0x0F]),
+            frame([0x01, 0x00]),
+            // The reset flag is set again
+            frame([0x05, 0x00]),
+            frame([0xFF, 0x24]),
+            frame([0x15, 0x00]),
+            // The previous frame failed, so no sample grab was read
+            frame([0x05, 0x03]),
+            frame([0x04, 0x00]),
+        ];
+        let frames: Vec<&[u8]> = frames.iter().map(|frame| &frame[..]).collect();
+        let mut adc = Ads131m::open_ads131m04(ReplaySpi::new(3, &frames));
+
+        let results: Vec<Option<Error>> = (0..frames.len())
+            .map(|_| adc.communicate(Command::new_null()).err())
+            .collect();
+        assert_eq!(
+            results,
+            [
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some(Error::UnexpectedResponse),
+                Some(Error::SendCrc),
+                None,
+                Some(Error::WordLengthChanged),
+            ]
+        );
+        assert_eq!(
+            adc.link_stats(),
+            LinkStats {
+                frames: 9,
+                receive_crc_errors: 0,
+                send_crc_errors: 1,
+                unexpected_responses: 1,
+                word_length_changes: 1,
+                resets: 2,
+                missed_samples: 1,
+            }
+        );
+
+        adc.clear_link_stats();
+        assert_eq!(adc.link_stats(), LinkStats::default());
+    }
+
+    #[test]
+    fn frame_info() {
+        let crc = Crc::<u16>::new(&CRC_16_IBM_3740);
+        let mut status = [0; 15];
+        status[..2].copy_from_slice(&[0x05, 0x05]);
+        let mut flipped = [0; 15];
+        flipped[..2].copy_from_slice(&[0xC5, 0x00]);
+        let intf = ReplaySpi::new(
+            3,
+            &[
+                &[0xFF, 0x24, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                &status,
+                &flipped,
+                &[0x24, 0x10, 0],
+            ],
+        );
+        let mut adc = Ads131m::open_ads131m04(intf);
+
+        let frame = adc.communicate(Command::new_null()).unwrap().frame;
+        assert_eq!(frame.response, [0xFF, 0x24]);
+        assert_eq!(frame.status, None);
+        assert_eq!(frame.data_ready, 0);
+
+        let frame = adc.communicate(Command::new_null()).unwrap().frame;
+        assert_eq!(frame.status, Some(Status::from_be_bytes([0x05, 0x05])));
+        assert!(frame.is_data_ready(Channel::Zero) && frame.is_data_ready(Channel::Two));
+        assert!(!frame.is_data_ready(Channel::One));
+        assert!(!frame.lock_changed && !frame.resync_changed);
+        assert_eq!(frame.crc, crc.checksum(&status));
+
+        let frame = adc
+            .communicate(Command::new_read_register(Address::Id))
+            .unwrap()
+            .frame;
+        assert_eq!(frame.data_ready, 0);
+        assert!(frame.lock_changed && frame.resync_changed);
+
+        let resp = adc.communicate(Command::new_null()).unwrap();
+        assert_eq!(resp.frame.response, [0x24, 0x10]);
+        assert_eq!(resp.frame.status, None);
+        assert!(!resp.frame.lock_changed);
+        assert_eq!(resp.frame.crc, crc.checksum(&[0x24, 0x10, 0]));
+    }
+
+    #[test]
+    fn detect() {
+        let mode = Mode {
+            spi_crc_enable: true,
+            ..Mode::default()
+        };
+        let intf = ReplaySpi::new(
+            3,
+            &[
+                &[0x12, 0x34, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                &[0x05, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                &[mode.to_be_bytes()[0], mode.to_be_bytes()[1], 0],
+                &[0x05, 0x00, 0, 0x12, 0x34, 0x56, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+            ],
+        );
+        let mut adc = Ads131m::detect_ads131m04(intf).unwrap();
+        assert!(adc.codec.mode_cache.spi_crc_enable);
+
+        let resp = adc.communicate(Command::new_null()).unwrap();
+        assert!(resp.status.is_some());
+        assert_eq!(resp.sample_grab.unwrap().into_i32_array()[0], 0x12_3456);
+    }
+
+    #[test]
+    fn detect_word_length() {
+        let mode = Mode {
+            word_length: WordLength::Bits16,
+            ..Mode::default()
+        };
+        let status: &[u8] = &[0x04, 0x00, 0, 0, 0, 0, 0, 0, 0, 0];
+        let intf = ReplaySpi::new(
+            2,
+            &[
+                // 24-bit CCITT and ANSI probes fail on the CRC
+                status,
+                status,
+                status,
+                status,
+                // 16-bit CCITT probe
+                status,
+                status,
+                &mode.to_be_bytes(),
+            ],
+        );
+        let adc = Ads131m::detect_ads131m04(intf).unwrap();
+        assert_eq!(adc.codec.mode_cache.word_packing, WordLength::Bits16);
+        assert_eq!(adc.codec.mode_cache.word_len, 2);
+        assert!(!adc.codec.mode_cache.spi_crc_enable);
+
+        let intf = adc.release();
+        assert!(intf.is_done());
+        assert_eq!(intf.sent[5][..2], [0xA1, 0x00]);
+    }
+
+    #[test]
+    fn any_model() {
+        let mut reset = [0; 27];
+        reset[..2].copy_from_slice(&[0xFF, 0x28]);
+        let mut sample = [0; 27];
+        sample[..2].copy_from_slice(&[0x05, 0x00]);
+        sample[24..27].copy_from_slice(&[0x12, 0x34, 0x56]);
+        let intf = ReplaySpi::new(3, &[&reset, &[0x28, 0x00, 0], &sample]);
+
+        let adc = AnyAds131m::open(intf).unwrap();
+        assert_eq!(adc.channel_count(), 8);
+        let AnyAds131m::Ads131m08(mut adc) = adc else {
+            panic!("wrong model");
+        };
+        // The undecodable first response is not counted
+        assert_eq!(
+            adc.link_stats(),
+            LinkStats {
+                frames: 1,
+                ..LinkStats::default()
+            }
+        );
+
+        let resp = adc.communicate(Command::new_null()).unwrap();
+        assert!(resp.status.is_some());
+        assert_eq!(resp.sample_grab.unwrap().into_i32_array()[7], 0x12_3456);
+        assert!(adc.release().is_done());
+    }
 }
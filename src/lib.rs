@@ -42,11 +42,32 @@
 #![no_std]
 
 mod sample_grab;
+#[cfg(feature = "serde")]
+mod serde_array;
 
+pub mod acquisition;
+#[cfg(feature = "async")]
+pub mod asynch;
+pub mod calibration;
+pub mod config;
+pub mod delay;
+pub mod digital;
+pub mod driver;
+#[cfg(feature = "sim")]
+pub mod fault;
 pub mod int;
 pub mod interface;
+pub mod profile;
+pub mod recovery;
 pub mod register;
+pub mod register_map;
+pub mod scaling;
+#[cfg(feature = "sim")]
+pub mod sim;
 pub mod spi;
+pub mod timing;
+
+use register::Address;
 
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
@@ -57,6 +78,8 @@ use serde::{Deserialize, Serialize};
 pub enum Error {
     /// Error from the SPI interface
     SpiIOError,
+    /// Error from a digital IO pin
+    PinIOError,
     /// CRC checksum error on a received SPI message
     ReceiveCrc {
         /// The computed CRC checksum
@@ -74,4 +97,37 @@ pub enum Error {
     WordLengthChanged,
     /// An unsupported channel was specified in a command
     UnsupportedChannel,
+    /// The device model does not match the model the driver was opened for
+    ModelMismatch {
+        /// The channel count the driver was opened for
+        expected: usize,
+        /// The channel count reported by the device
+        found: usize,
+    },
+    /// A sampled signal had too few zero crossings to calibrate against
+    InsufficientSignal,
+    /// A calibration profile blob was truncated, corrupted, or saved for a different model
+    InvalidProfile,
+    /// A buffer was too small to hold the data written to it
+    BufferTooSmall,
+    /// The device's register map CRC did not match the CRC of the registers written to it
+    RegisterMapCrcMismatch {
+        /// The CRC computed from the registers written to the device
+        expected: u16,
+        /// The CRC read from the device
+        read: u16,
+    },
+    /// A configuration is not supported by the device model, or is an invalid combination of settings
+    InvalidConfig,
+    /// A multi-register command addressed no registers, or extended past the end of the register map
+    InvalidRegisterCount,
+    /// A register did not read back the value written to it
+    VerifyMismatch {
+        /// The address of the register
+        address: Address,
+        /// The value written to the register
+        wrote: [u8; 2],
+        /// The value read back from the register
+        read: [u8; 2],
+    },
 }
@@ -0,0 +1,481 @@
+//! Persistent calibration profiles
+
+use crc::{Crc, CRC_16_IBM_3740};
+
+use crate::delay::Delay;
+use crate::driver::{Driver, SampleSink};
+use crate::register::{
+    Address, Channel, ChannelConfig, ChannelGainCal, ChannelGainCalLsb, ChannelGainCalMsb,
+    ChannelOffsetCal, ChannelOffsetCalLsb, ChannelOffsetCalMsb, ChannelSpecific, Clock, Config,
+    Gain1, Gain2, Global, Mode, RegistryMapCrc, Threshold, ThresholdLsb, ThresholdMsb,
+};
+use crate::register_map::RegisterMap;
+use crate::spi::Transfer;
+use crate::Error;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The version of the calibration profile blob format
+const BLOB_VERSION: u8 = 1;
+
+/// The number of registers stored for each channel
+const CHANNEL_REGISTERS: usize = 5;
+
+/// The calibration of a single channel
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ChannelCalibration {
+    /// Channel configuration, including the phase delay and input selection
+    pub config: ChannelConfig,
+
+    /// Channel offset calibration
+    pub offset_cal: ChannelOffsetCal,
+
+    /// Channel gain calibration
+    pub gain_cal: ChannelGainCal,
+}
+
+impl ChannelCalibration {
+    /// The registers of this channel in address order, MSB first
+    #[allow(clippy::similar_names)]
+    pub(crate) fn to_words(self) -> [[u8; 2]; CHANNEL_REGISTERS] {
+        let (offset_msb, offset_lsb) = self.offset_cal.into_parts();
+        let (gain_msb, gain_lsb) = self.gain_cal.into_parts();
+
+        [
+            self.config.to_be_bytes(),
+            offset_msb.to_be_bytes(),
+            offset_lsb.to_be_bytes(),
+            gain_msb.to_be_bytes(),
+            gain_lsb.to_be_bytes(),
+        ]
+    }
+
+    pub(crate) fn from_words(words: &[[u8; 2]]) -> Self {
+        Self {
+            config: ChannelConfig::from_be_bytes(words[0]),
+            offset_cal: ChannelOffsetCal::from_parts(
+                ChannelOffsetCalMsb::from_be_bytes(words[1]),
+                ChannelOffsetCalLsb::from_be_bytes(words[2]),
+            ),
+            gain_cal: ChannelGainCal::from_parts(
+                ChannelGainCalMsb::from_be_bytes(words[3]),
+                ChannelGainCalLsb::from_be_bytes(words[4]),
+            ),
+        }
+    }
+}
+
+/// A calibration profile for every channel of a device
+///
+/// This captures the registers which are set by calibration, so they can be stored
+/// and restored to the device later. Profiles can be stored as a compact binary blob
+/// with [`to_blob`](Self::to_blob), or with `serde`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CalibrationProfile<const CHANNELS: usize> {
+    /// The calibration of each channel
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_array"))]
+    pub channels: [ChannelCalibration; CHANNELS],
+
+    /// PGA gain for channels 0 to 3
+    pub gain1: Gain1,
+
+    /// PGA gain for channels 4 to 7
+    pub gain2: Gain2,
+
+    /// Current-detect threshold and DC block filter
+    pub threshold: Threshold,
+}
+
+impl<const CHANNELS: usize> CalibrationProfile<CHANNELS> {
+    /// The length of a calibration profile blob, in bytes
+    ///
+    /// The blob holds a version byte, the channel count, each register MSB first, and a CCITT CRC
+    pub const BLOB_LEN: usize = 2 + 2 * (4 + CHANNEL_REGISTERS * CHANNELS) + 2;
+
+    /// Read the calibration profile from the device
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed
+    pub fn read<S, W, P, D>(
+        driver: &mut Driver<S, W, CHANNELS, P, D>,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<Self, Error>
+    where
+        S: Transfer<W>,
+        W: Copy,
+        D: Delay,
+    {
+        let gains = driver.read_registers(Address::Gain1, 2, sink)?;
+        let threshold = driver.read_registers(Address::ThresholdMsb, 2, sink)?;
+        let channels = driver.read_registers(
+            Address::ChannelConfig(Channel::Zero),
+            Self::channel_register_count(),
+            sink,
+        )?;
+
+        let mut globals = [[0; 2]; 4];
+        globals[..2].copy_from_slice(gains.words());
+        globals[2..].copy_from_slice(threshold.words());
+
+        Ok(Self::from_words(globals, channels.words()))
+    }
+
+    /// Write the calibration profile to the device
+    ///
+    /// `GAIN2` is only written on devices with more than 4 channels.
+    /// Once the profile is written, the expected register map CRC is computed from the profile
+    /// and the other registers read from the device, and compared with the `REGMAP_CRC` register
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed, the device did not
+    /// acknowledge a write, or the register map CRC did not match
+    pub fn apply<S, W, P, D>(
+        &self,
+        driver: &mut Driver<S, W, CHANNELS, P, D>,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error>
+    where
+        S: Transfer<W>,
+        W: Copy,
+        D: Delay,
+    {
+        let globals = self.global_words();
+        // GAIN2 is a reserved address on devices with 4 or fewer channels
+        let gains = if RegisterMap::<CHANNELS>::read_back_mask(Address::Gain2) == [0; 2] {
+            &globals[..1]
+        } else {
+            &globals[..2]
+        };
+        driver.write_registers(Address::Gain1, gains, sink)?;
+        driver.write_registers(Address::ThresholdMsb, &globals[2..], sink)?;
+
+        let mut channels = [[0; 2]; CHANNEL_REGISTERS * 8];
+        let channels = &mut channels[..CHANNEL_REGISTERS * CHANNELS];
+        for (words, channel) in channels
+            .chunks_exact_mut(CHANNEL_REGISTERS)
+            .zip(self.channels)
+        {
+            words.copy_from_slice(&channel.to_words());
+        }
+        driver.write_registers(Address::ChannelConfig(Channel::Zero), channels, sink)?;
+
+        // MODE, CLOCK, GAIN1, GAIN2 and CFG
+        let unchanged = driver.read_registers(Address::Mode, 5, sink)?;
+        let [mode, clock, _, _, config] = unchanged.words() else {
+            return Err(Error::UnexpectedResponse);
+        };
+
+        let mut map = RegisterMap {
+            mode: Mode::from_be_bytes(*mode),
+            clock: Clock::from_be_bytes(*clock),
+            config: Config::from_be_bytes(*config),
+            ..RegisterMap::default()
+        };
+        map.set_profile(self);
+
+        let expected = map.crc();
+        let read = driver.read_register::<RegistryMapCrc>(sink)?.crc;
+        if read != expected {
+            return Err(Error::RegisterMapCrcMismatch { expected, read });
+        }
+
+        Ok(())
+    }
+
+    /// Write the profile to `buf` as a binary blob, returning the length of the blob
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if `buf` is shorter than [`BLOB_LEN`](Self::BLOB_LEN)
+    pub fn to_blob(&self, buf: &mut [u8]) -> Result<usize, Error> {
+        let blob = buf.get_mut(..Self::BLOB_LEN).ok_or(Error::BufferTooSmall)?;
+
+        blob[0] = BLOB_VERSION;
+        blob[1] = u8::try_from(CHANNELS).map_err(|_| Error::UnsupportedChannel)?;
+
+        let words = self
+            .global_words()
+            .into_iter()
+            .chain(self.channels.iter().flat_map(|channel| channel.to_words()));
+        for (bytes, word) in blob[2..].chunks_exact_mut(2).zip(words) {
+            bytes.copy_from_slice(&word);
+        }
+
+        let crc_idx = Self::BLOB_LEN - 2;
+        let crc = Crc::<u16>::new(&CRC_16_IBM_3740).checksum(&blob[..crc_idx]);
+        blob[crc_idx..].copy_from_slice(&crc.to_be_bytes());
+
+        Ok(Self::BLOB_LEN)
+    }
+
+    /// Read a profile from a binary blob written by [`to_blob`](Self::to_blob)
+    ///
+    /// Any bytes after the blob are ignored
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the blob is truncated, fails its CRC check,
+    /// or was written by a different version or for a different channel count
+    pub fn from_blob(blob: &[u8]) -> Result<Self, Error> {
+        let blob = blob.get(..Self::BLOB_LEN).ok_or(Error::InvalidProfile)?;
+
+        let crc_idx = Self::BLOB_LEN - 2;
+        let crc = Crc::<u16>::new(&CRC_16_IBM_3740).checksum(&blob[..crc_idx]);
+        if blob[crc_idx..] != crc.to_be_bytes()
+            || blob[0] != BLOB_VERSION
+            || usize::from(blob[1]) != CHANNELS
+        {
+            return Err(Error::InvalidProfile);
+        }
+
+        let mut words = [[0; 2]; 4 + CHANNEL_REGISTERS * 8];
+        let words = &mut words[..4 + CHANNEL_REGISTERS * CHANNELS];
+        for (word, bytes) in words.iter_mut().zip(blob[2..crc_idx].chunks_exact(2)) {
+            word.copy_from_slice(bytes);
+        }
+
+        let mut globals = [[0; 2]; 4];
+        globals.copy_from_slice(&words[..4]);
+
+        Ok(Self::from_words(globals, &words[4..]))
+    }
+
+    /// The `GAIN1`, `GAIN2`, `THRSHLD_MSB` and `THRSHLD_LSB` registers, MSB first
+    #[allow(clippy::similar_names)]
+    fn global_words(&self) -> [[u8; 2]; 4] {
+        let (threshold_msb, threshold_lsb) = self.threshold.into_parts();
+
+        [
+            self.gain1.to_be_bytes(),
+            self.gain2.to_be_bytes(),
+            threshold_msb.to_be_bytes(),
+            threshold_lsb.to_be_bytes(),
+        ]
+    }
+
+    fn from_words(globals: [[u8; 2]; 4], channels: &[[u8; 2]]) -> Self {
+        Self {
+            channels: core::array::from_fn(|channel| {
+                let start = channel * CHANNEL_REGISTERS;
+                ChannelCalibration::from_words(&channels[start..start + CHANNEL_REGISTERS])
+            }),
+            gain1: Gain1::from_be_bytes(globals[0]),
+            gain2: Gain2::from_be_bytes(globals[1]),
+            threshold: Threshold::from_parts(
+                ThresholdMsb::from_be_bytes(globals[2]),
+                ThresholdLsb::from_be_bytes(globals[3]),
+            ),
+        }
+    }
+
+    /// The number of channel-specific registers for the device
+    #[allow(clippy::cast_possible_truncation)]
+    const fn channel_register_count() -> u8 {
+        (CHANNEL_REGISTERS * CHANNELS) as u8
+    }
+}
+
+impl<const CHANNELS: usize> Default for CalibrationProfile<CHANNELS> {
+    fn default() -> Self {
+        Self {
+            channels: [ChannelCalibration::default(); CHANNELS],
+            gain1: Gain1::default(),
+            gain2: Gain2::default(),
+            threshold: Threshold::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::driver::Discard;
+    use crate::int::{i10, i24, u24};
+    use crate::register::{ChannelMux, PgaGain};
+    use crate::spi::replay::{frame, open};
+
+    fn profile() -> CalibrationProfile<4> {
+        let mut profile = CalibrationProfile::default();
+        profile.channels[1] = ChannelCalibration {
+            config: ChannelConfig {
+                phase: i10::try_new(-17).unwrap(),
+                dc_block_disable: true,
+                mux: ChannelMux::AnalogIn,
+            },
+            offset_cal: ChannelOffsetCal {
+                offset: i24::try_new(-1234).unwrap(),
+            },
+            gain_cal: ChannelGainCal {
+                gain: u24::try_new(0x81_2345).unwrap(),
+            },
+        };
+        profile.gain1.pga_gain3 = PgaGain::Gain8;
+        profile.threshold.current_detect_threshold = i24::try_new(0x12_3456).unwrap();
+
+        profile
+    }
+
+    #[test]
+    fn blob_round_trip() {
+        let profile = profile();
+        let mut blob = [0xAA; 64];
+        assert_eq!(
+            profile.to_blob(&mut blob),
+            Ok(CalibrationProfile::<4>::BLOB_LEN)
+        );
+        assert_eq!(CalibrationProfile::<4>::from_blob(&blob), Ok(profile));
+
+        assert_eq!(profile.to_blob(&mut [0; 10]), Err(Error::BufferTooSmall));
+    }
+
+    #[test]
+    fn blob_invalid() {
+        let mut blob = [0; CalibrationProfile::<4>::BLOB_LEN];
+        profile().to_blob(&mut blob).unwrap();
+
+        assert_eq!(
+            CalibrationProfile::<4>::from_blob(&blob[..blob.len() - 1]),
+            Err(Error::InvalidProfile)
+        );
+        assert_eq!(
+            CalibrationProfile::<3>::from_blob(&blob),
+            Err(Error::InvalidProfile)
+        );
+
+        blob[10] ^= 0x01;
+        assert_eq!(
+            CalibrationProfile::<4>::from_blob(&blob),
+            Err(Error::InvalidProfile)
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let profile = profile();
+        let unchanged = [
+            Mode::default().to_be_bytes(),
+            Clock::default().to_be_bytes(),
+            profile.gain1.to_be_bytes(),
+            [0, 0],
+            Config::default().to_be_bytes(),
+        ];
+        let mut map = RegisterMap::default();
+        map.set_profile(&profile);
+        let crc = map.crc();
+        let status = frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]);
+
+        let mut ack = [[0xE1, 0x04]; 6];
+        ack[1..].copy_from_slice(&unchanged);
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x42, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            status.clone(),
+            frame(&[[0x43, 0x81], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            status.clone(),
+            frame(&[[0x44, 0x93], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            status.clone(),
+            frame(&ack),
+            status,
+            frame(&[RegistryMapCrc { crc }.to_be_bytes()]),
+        ]);
+
+        profile.apply(&mut driver, &mut Discard).unwrap();
+
+        let intf = driver.into_inner().release();
+        assert!(intf.is_done());
+        // Only GAIN1 is written, as GAIN2 is reserved on an ADS131M04
+        assert_eq!(intf.sent[0][..3], [0x62, 0x00, 0x00]);
+        for sent in &intf.sent {
+            let opcode = u16::from_be_bytes([sent[0], sent[1]]);
+            if opcode >> 13 == 0b011 {
+                let first = (opcode >> 7) & 0x3F;
+                let count = (opcode & 0x7F) + 1;
+                assert!(!(first..first + count).contains(&0x05));
+            }
+        }
+        // Channel 1 offset calibration and gain calibration MSB
+        let channel1 = 3 * (2 + CHANNEL_REGISTERS);
+        assert_eq!(
+            intf.sent[4][channel1..channel1 + 9],
+            [0xFF, 0xFB, 0x00, 0x2E, 0x00, 0x00, 0x81, 0x23, 0x00]
+        );
+    }
+
+    #[test]
+    fn apply_crc_mismatch() {
+        let status = frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]);
+        let mut ack = [[0; 2]; 6];
+        ack[0] = [0xE1, 0x04];
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&[[0x42, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            status.clone(),
+            frame(&[[0x43, 0x81], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            status.clone(),
+            frame(&[[0x44, 0x93], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            status.clone(),
+            frame(&ack),
+            status,
+            frame(&[[0x12, 0x34]]),
+        ]);
+
+        let profile = CalibrationProfile::<4>::default();
+        let expected = RegisterMap::<4> {
+            mode: Mode::from_be_bytes([0; 2]),
+            clock: Clock::from_be_bytes([0; 2]),
+            config: Config::from_be_bytes([0; 2]),
+            ..RegisterMap::default()
+        }
+        .crc();
+        assert_eq!(
+            profile.apply(&mut driver, &mut Discard),
+            Err(Error::RegisterMapCrcMismatch {
+                expected,
+                read: 0x1234
+            })
+        );
+    }
+
+    #[test]
+    fn read() {
+        let profile = profile();
+        let status = frame(&[[0x05, 0x00], [0, 0], [0, 0], [0, 0], [0, 0]]);
+        let globals = profile.global_words();
+
+        let mut gains = Vec::from([[0xE2, 0x01]]);
+        gains.extend_from_slice(&globals[..2]);
+        let mut threshold = Vec::from([[0xE3, 0x81]]);
+        threshold.extend_from_slice(&globals[2..]);
+        let mut channels = Vec::from([[0xE4, 0x93]]);
+        channels.extend(
+            profile
+                .channels
+                .iter()
+                .flat_map(|channel| channel.to_words()),
+        );
+
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&gains),
+            status.clone(),
+            frame(&threshold),
+            status,
+            frame(&channels),
+        ]);
+
+        assert_eq!(
+            CalibrationProfile::read(&mut driver, &mut Discard),
+            Ok(profile)
+        );
+        assert!(driver.into_inner().release().is_done());
+    }
+}
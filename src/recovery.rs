@@ -0,0 +1,529 @@
+//! Automatic recovery from communication errors and device resets
+
+use crate::delay::{Delay, NoDelay};
+use crate::digital::NoPin;
+use crate::driver::{Driver, SampleSink};
+use crate::interface::{Command, Response};
+use crate::register::Address;
+use crate::register_map::{AddressSet, RegisterMap};
+use crate::spi::Transfer;
+use crate::Error;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// How [`Recovering`] responds to errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RecoveryPolicy {
+    /// The number of times a command is retried after a CRC error
+    pub max_retries: u8,
+
+    /// Probe the communication settings of the device when the word length changes,
+    /// a response is unexpected, or a command still fails after every retry
+    pub resynchronize: bool,
+
+    /// Write the register configuration back to the device after it was reset
+    ///
+    /// Otherwise the driver's shadow is reset to the default register values, to match the device
+    pub restore_registers: bool,
+}
+
+impl Default for RecoveryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            resynchronize: true,
+            restore_registers: true,
+        }
+    }
+}
+
+/// An action taken to recover from an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RecoveryEvent {
+    /// A command was sent again after it failed with the CRC error `error`
+    ///
+    /// Commands sent again after resynchronizing are recorded as [`Resynchronized`](Self::Resynchronized)
+    Retried {
+        /// The error the command failed with
+        error: Error,
+    },
+    /// The communication settings of the device were probed after an error
+    Resynchronized {
+        /// Whether the device had been reset
+        reset: bool,
+    },
+    /// The register configuration was written back to the device after it was reset
+    Restored {
+        /// The number of registers written
+        registers: usize,
+    },
+    /// Recovery was abandoned, and `error` was returned
+    Failed {
+        /// The error returned
+        error: Error,
+    },
+}
+
+/// A receiver for the actions taken to recover from errors
+pub trait RecoveryLog {
+    /// Record a recovery event
+    fn record(&mut self, event: RecoveryEvent);
+}
+
+impl<F> RecoveryLog for F
+where
+    F: FnMut(RecoveryEvent),
+{
+    fn record(&mut self, event: RecoveryEvent) {
+        self(event);
+    }
+}
+
+/// A [`RecoveryLog`] which drops every event it receives
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct NoLog;
+
+impl RecoveryLog for NoLog {
+    fn record(&mut self, _event: RecoveryEvent) {}
+}
+
+/// Counts of the actions taken to recover from errors
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RecoveryStats {
+    /// Commands sent again after a CRC error, without resynchronizing first
+    pub retries: u32,
+
+    /// Times the communication settings of the device were probed
+    pub resynchronizations: u32,
+
+    /// Device resets detected
+    pub resets: u32,
+
+    /// Times the register configuration was written back to the device
+    pub restores: u32,
+
+    /// Operations which failed after every recovery attempt
+    pub failures: u32,
+}
+
+/// A driver wrapper which recovers from communication errors and device resets
+///
+/// Each operation is retried after a CRC error. If the word length changes, a response is unexpected,
+/// or the retries run out, the communication settings of the device are probed to find out if it was reset.
+/// The device is also known to have been reset if a status word has the reset flag set,
+/// once the flag has been cleared in the configuration.
+///
+/// After a reset, the register configuration is written back to the device. This is the configuration
+/// given to [`with_config`](Self::with_config), or otherwise every register in the driver's shadow.
+///
+/// Retrying an operation sends its command again, which is only safe for commands that can be repeated.
+/// Every command the driver sends can be, though reads of the `STATUS` register clear its error flags.
+pub struct Recovering<
+    S: Transfer<W>,
+    W: Copy,
+    const CHANNELS: usize,
+    P = NoPin,
+    D = NoDelay,
+    L = NoLog,
+> {
+    driver: Driver<S, W, CHANNELS, P, D>,
+    policy: RecoveryPolicy,
+    config: Option<RegisterMap<CHANNELS>>,
+    log: L,
+    stats: RecoveryStats,
+}
+
+impl<S, W, const CHANNELS: usize, P, D> Recovering<S, W, CHANNELS, P, D>
+where
+    S: Transfer<W>,
+    W: Copy,
+    D: Delay,
+{
+    /// Wrap `driver`, recovering from errors as `policy` describes
+    pub const fn new(driver: Driver<S, W, CHANNELS, P, D>, policy: RecoveryPolicy) -> Self {
+        Self {
+            driver,
+            policy,
+            config: None,
+            log: NoLog,
+            stats: RecoveryStats {
+                retries: 0,
+                resynchronizations: 0,
+                resets: 0,
+                restores: 0,
+                failures: 0,
+            },
+        }
+    }
+}
+
+impl<S, W, const CHANNELS: usize, P, D, L> Recovering<S, W, CHANNELS, P, D, L>
+where
+    S: Transfer<W>,
+    W: Copy,
+    D: Delay,
+    L: RecoveryLog,
+{
+    /// Restore `config` after a device reset, instead of the driver's shadow
+    #[must_use]
+    pub const fn with_config(mut self, config: RegisterMap<CHANNELS>) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Report every recovery event to `log`
+    pub fn with_log<L2: RecoveryLog>(self, log: L2) -> Recovering<S, W, CHANNELS, P, D, L2> {
+        Recovering {
+            driver: self.driver,
+            policy: self.policy,
+            config: self.config,
+            log,
+            stats: self.stats,
+        }
+    }
+
+    /// Send a command and return the device's response to it, as [`Driver::execute`] does
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed after every recovery attempt
+    pub fn execute(
+        &mut self,
+        command: Command,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<Response<CHANNELS>, Error> {
+        self.recover(sink, |driver, sink| driver.execute(command, sink))
+    }
+
+    /// Exchange a null command and return the whole frame received, as [`Driver::read_frame`] does
+    ///
+    /// A frame lost to an error is not read again, as the next frame holds a newer sample grab
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed after every recovery attempt
+    pub fn read_frame(
+        &mut self,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<Response<CHANNELS>, Error> {
+        self.recover(sink, |driver, _| driver.read_frame())
+    }
+
+    /// The wrapped driver
+    pub const fn driver(&self) -> &Driver<S, W, CHANNELS, P, D> {
+        &self.driver
+    }
+
+    /// Mutable access to the wrapped driver, for operations without recovery
+    pub const fn driver_mut(&mut self) -> &mut Driver<S, W, CHANNELS, P, D> {
+        &mut self.driver
+    }
+
+    /// Counts of the recovery actions taken so far
+    pub const fn stats(&self) -> RecoveryStats {
+        self.stats
+    }
+
+    /// Reset every recovery count to zero
+    pub fn clear_stats(&mut self) {
+        self.stats = RecoveryStats::default();
+    }
+
+    /// Stop recovering, and return the driver and recovery log
+    pub fn release(self) -> (Driver<S, W, CHANNELS, P, D>, L) {
+        (self.driver, self.log)
+    }
+
+    /// Run `operation`, recovering from any errors
+    fn recover<Sink: SampleSink<CHANNELS>>(
+        &mut self,
+        sink: &mut Sink,
+        mut operation: impl FnMut(
+            &mut Driver<S, W, CHANNELS, P, D>,
+            &mut Sink,
+        ) -> Result<Response<CHANNELS>, Error>,
+    ) -> Result<Response<CHANNELS>, Error> {
+        let mut retries = 0;
+        let mut resynchronized = false;
+
+        loop {
+            let error = match operation(&mut self.driver, sink) {
+                Ok(resp) => {
+                    if resp.status.is_some_and(|status| status.reset) {
+                        let (config, known) = self.configuration();
+                        if !config.mode.reset && known.contains(Address::Mode) {
+                            self.stats.resets = self.stats.resets.saturating_add(1);
+                            self.restore(&config, known, sink)?;
+                        }
+                    }
+
+                    return Ok(resp);
+                }
+                Err(error) => error,
+            };
+
+            let retry = matches!(error, Error::ReceiveCrc { .. } | Error::SendCrc)
+                && retries < self.policy.max_retries;
+            let resynchronize = matches!(
+                error,
+                Error::ReceiveCrc { .. }
+                    | Error::SendCrc
+                    | Error::WordLengthChanged
+                    | Error::UnexpectedResponse
+            ) && self.policy.resynchronize
+                && !resynchronized;
+
+            if retry {
+                retries += 1;
+                self.stats.retries = self.stats.retries.saturating_add(1);
+                self.log.record(RecoveryEvent::Retried { error });
+            } else if resynchronize {
+                resynchronized = true;
+                self.resynchronize(sink)?;
+            } else {
+                return Err(self.fail(error));
+            }
+        }
+    }
+
+    /// Probe the communication settings of the device, and restore the configuration if it was reset
+    fn resynchronize(&mut self, sink: &mut impl SampleSink<CHANNELS>) -> Result<(), Error> {
+        // Probing overwrites the shadow `MODE` register, so take the configuration first
+        let (config, known) = self.configuration();
+        let mode = match self.driver.detect_mode() {
+            Ok(mode) => mode,
+            Err(error) => return Err(self.fail(error)),
+        };
+        let reset = mode.reset && !config.mode.reset && known.contains(Address::Mode);
+
+        self.stats.resynchronizations = self.stats.resynchronizations.saturating_add(1);
+        self.log.record(RecoveryEvent::Resynchronized { reset });
+
+        if reset {
+            self.stats.resets = self.stats.resets.saturating_add(1);
+            self.restore(&config, known, sink)?;
+        }
+
+        Ok(())
+    }
+
+    /// The register configuration to restore, and the registers in it which are known
+    fn configuration(&self) -> (RegisterMap<CHANNELS>, AddressSet) {
+        self.config.map_or_else(
+            || (*self.driver.shadow(), self.driver.shadowed()),
+            |config| (config, RegisterMap::<CHANNELS>::addresses().collect()),
+        )
+    }
+
+    /// Write the known registers in `config` back to the device, in address order
+    fn restore(
+        &mut self,
+        config: &RegisterMap<CHANNELS>,
+        known: AddressSet,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<(), Error> {
+        if !self.policy.restore_registers {
+            // The device is left in its reset state, so the shadow must match it
+            self.driver.reset_shadow();
+            return Ok(());
+        }
+
+        let mut registers = 0;
+        // Reserved addresses, such as GAIN2 on devices with 4 or fewer channels, are skipped
+        let writable = |&address: &Address| {
+            known.contains(address) && RegisterMap::<CHANNELS>::read_back_mask(address) != [0; 2]
+        };
+        for address in RegisterMap::<CHANNELS>::addresses().filter(writable) {
+            if let Some(word) = config.get(address) {
+                if let Err(error) = self.driver.write_registers(address, &[word], sink) {
+                    return Err(self.fail(error));
+                }
+                registers += 1;
+            }
+        }
+
+        self.stats.restores = self.stats.restores.saturating_add(1);
+        self.log.record(RecoveryEvent::Restored { registers });
+
+        Ok(())
+    }
+
+    /// Record a failure, and return `error`
+    fn fail(&mut self, error: Error) -> Error {
+        self.stats.failures = self.stats.failures.saturating_add(1);
+        self.log.record(RecoveryEvent::Failed { error });
+
+        error
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::driver::Discard;
+    use crate::fault::{Fault, FaultInjector};
+    use crate::interface::Ads131m;
+    use crate::register::{Gain1, Mode, PgaGain, WordLength};
+    use crate::sim::Simulator;
+
+    type Intf = FaultInjector<Simulator<4>>;
+
+    const GAIN1: Gain1 = Gain1 {
+        pga_gain0: PgaGain::Gain8,
+        pga_gain1: PgaGain::Gain1,
+        pga_gain2: PgaGain::Gain1,
+        pga_gain3: PgaGain::Gain1,
+    };
+
+    fn open(intf: Intf) -> Recovering<Intf, u8, 4> {
+        Recovering::new(
+            Driver::new(Ads131m::open_ads131m04(intf)),
+            RecoveryPolicy::default(),
+        )
+    }
+
+    /// Configure the simulated device, then reset it on the first transfer after the configuration
+    fn configured(word_length: WordLength) -> Recovering<Intf, u8, 4> {
+        let mut intf = FaultInjector::new(Simulator::new()).with_reset(Simulator::hardware_reset);
+        intf.schedule(4, Fault::DeviceReset).unwrap();
+        let mut driver = Driver::new(Ads131m::open_ads131m04(intf));
+
+        let mode = Mode {
+            reset: false,
+            word_length,
+            ..Mode::default()
+        };
+        driver.write_register(mode, &mut Discard).unwrap();
+        driver.write_register(GAIN1, &mut Discard).unwrap();
+
+        Recovering::new(driver, RecoveryPolicy::default())
+    }
+
+    fn simulator<L: RecoveryLog>(
+        recovering: Recovering<Intf, u8, 4, NoPin, NoDelay, L>,
+    ) -> Simulator<4> {
+        recovering.release().0.into_inner().release().release()
+    }
+
+    #[test]
+    fn retry() {
+        let mut intf = FaultInjector::new(Simulator::<4>::new());
+        intf.schedule(0, Fault::FlipReceivedBit { bit: 30 })
+            .unwrap();
+        let mut recovering = open(intf);
+
+        assert!(recovering.read_frame(&mut Discard).is_ok());
+        assert_eq!(
+            recovering.stats(),
+            RecoveryStats {
+                retries: 1,
+                ..RecoveryStats::default()
+            }
+        );
+    }
+
+    #[test]
+    fn brown_out() {
+        let mut events = Vec::new();
+        let mut recovering = configured(WordLength::Bits16).with_log(|event| events.push(event));
+
+        assert!(recovering.read_frame(&mut Discard).is_ok());
+        assert!(recovering.read_frame(&mut Discard).is_ok());
+
+        let stats = recovering.stats();
+        assert_eq!(stats.resynchronizations, 1);
+        assert_eq!(stats.resets, 1);
+        assert_eq!(stats.restores, 1);
+        assert_eq!(stats.failures, 0);
+
+        let (driver, _) = recovering.release();
+        let sim = driver.into_inner().release().release();
+        assert_eq!(sim.registers().mode.word_length, WordLength::Bits16);
+        assert!(!sim.registers().mode.reset);
+        assert_eq!(sim.registers().gain1, GAIN1);
+
+        assert!(events.contains(&RecoveryEvent::Resynchronized { reset: true }));
+        assert!(events.contains(&RecoveryEvent::Restored { registers: 2 }));
+    }
+
+    #[test]
+    fn spurious_reset() {
+        let mut recovering = configured(WordLength::Bits24);
+
+        assert!(recovering.read_frame(&mut Discard).is_ok());
+        assert_eq!(
+            recovering.stats(),
+            RecoveryStats {
+                retries: 0,
+                resynchronizations: 1,
+                resets: 1,
+                restores: 1,
+                failures: 0,
+            }
+        );
+
+        let sim = simulator(recovering);
+        assert!(!sim.registers().mode.reset);
+        assert_eq!(sim.registers().gain1, GAIN1);
+    }
+
+    #[test]
+    fn supplied_config() {
+        let mut config = RegisterMap::default();
+        config.mode.reset = false;
+        config.gain1.pga_gain1 = PgaGain::Gain4;
+        let mut events = Vec::new();
+        let mut recovering = configured(WordLength::Bits24)
+            .with_config(config)
+            .with_log(|event| events.push(event));
+
+        assert!(recovering.read_frame(&mut Discard).is_ok());
+        assert_eq!(recovering.stats().restores, 1);
+        assert_eq!(simulator(recovering).registers().gain1, config.gain1);
+        // Every register except GAIN2, which is reserved on an ADS131M04
+        assert!(events.contains(&RecoveryEvent::Restored { registers: 26 }));
+    }
+
+    #[test]
+    fn no_restore() {
+        let policy = RecoveryPolicy {
+            restore_registers: false,
+            ..RecoveryPolicy::default()
+        };
+        let (driver, _) = configured(WordLength::Bits24).release();
+        let mut recovering = Recovering::new(driver, policy);
+
+        assert!(recovering.read_frame(&mut Discard).is_ok());
+        assert_eq!(recovering.stats().resets, 1);
+        assert_eq!(recovering.stats().restores, 0);
+
+        // The shadow no longer holds the configuration lost in the reset
+        let (driver, _) = recovering.release();
+        assert_eq!(driver.shadow(), &RegisterMap::default().masked());
+        let sim = driver.into_inner().release().release();
+        assert_eq!(sim.registers().gain1, Gain1::default());
+        assert_eq!(sim.registers(), &RegisterMap::default().masked());
+    }
+
+    #[test]
+    fn failure() {
+        let mut intf = FaultInjector::new(Simulator::<4>::new());
+        intf.schedule(0, Fault::SpiError).unwrap();
+        let mut recovering = open(intf);
+
+        assert_eq!(
+            recovering.read_frame(&mut Discard).err(),
+            Some(Error::SpiIOError)
+        );
+        assert_eq!(recovering.stats().failures, 1);
+        assert_eq!(recovering.stats().retries, 0);
+    }
+}
@@ -17,7 +17,7 @@ macro_rules! is_bit_set {
 }
 
 /// An ADC channel
-#[derive(Debug, PartialEq, Eq, Clone, Copy, IntoPrimitive)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, IntoPrimitive, TryFromPrimitive)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[repr(u8)]
 pub enum Channel {
@@ -164,6 +164,39 @@ impl Address {
         }
     }
 
+    /// Get the `RegisterAddress` for an address value, if one exists
+    pub(crate) fn from_address(address: u8) -> Option<Self> {
+        const FIRST_CHANNEL_ADDRESS: u8 = 0x9;
+        const LAST_CHANNEL_ADDRESS: u8 = 0x30;
+
+        let addr = match address {
+            0x0 => Self::Id,
+            0x1 => Self::Status,
+            0x2 => Self::Mode,
+            0x3 => Self::Clock,
+            0x4 => Self::Gain1,
+            0x5 => Self::Gain2,
+            0x6 => Self::Config,
+            0x7 => Self::ThresholdMsb,
+            0x8 => Self::ThresholdLsb,
+            FIRST_CHANNEL_ADDRESS..=LAST_CHANNEL_ADDRESS => {
+                let offset = address - FIRST_CHANNEL_ADDRESS;
+                let channel = Channel::try_from(offset / 5).ok()?;
+                match offset % 5 {
+                    0 => Self::ChannelConfig(channel),
+                    1 => Self::ChannelOffsetCalMsb(channel),
+                    2 => Self::ChannelOffsetCalLsb(channel),
+                    3 => Self::ChannelGainCalMsb(channel),
+                    _ => Self::ChannelGainCalLsb(channel),
+                }
+            }
+            0x3E => Self::RegisterMapCrc,
+            _ => return None,
+        };
+
+        Some(addr)
+    }
+
     /// Get the channel for the register, if applicable
     pub(crate) const fn channel(self) -> Option<Channel> {
         match self {
@@ -196,6 +229,17 @@ where
     /// Words are MSB first
     #[must_use]
     fn to_be_bytes(self) -> [u8; 2];
+
+    /// The bits of the register which read back the value written to them
+    /// on a device with `channel_count` channels
+    ///
+    /// Read only and reserved bits, and bits with no effect on the device model, are cleared
+    ///
+    /// Words are MSB first
+    #[must_use]
+    fn read_back_mask(_channel_count: usize) -> [u8; 2] {
+        [0xFF, 0xFF]
+    }
 }
 
 /// A channel-specific device register
@@ -218,6 +262,17 @@ where
     /// Words are MSB first
     #[must_use]
     fn to_be_bytes(self) -> [u8; 2];
+
+    /// The bits of the register which read back the value written to them
+    /// on a device with `channel_count` channels
+    ///
+    /// Read only and reserved bits, and bits with no effect on the device model, are cleared
+    ///
+    /// Words are MSB first
+    #[must_use]
+    fn read_back_mask(_channel_count: usize) -> [u8; 2] {
+        [0xFF, 0xFF]
+    }
 }
 
 /// SPI Word size configuration
@@ -354,6 +409,23 @@ pub enum OversamplingRatio {
     Osr16256 = 7,
 }
 
+impl OversamplingRatio {
+    /// The number of modulator clock cycles per conversion
+    #[must_use]
+    pub const fn ratio(self) -> u16 {
+        match self {
+            Self::Osr128 => 128,
+            Self::Osr256 => 256,
+            Self::Osr512 => 512,
+            Self::Osr1024 => 1024,
+            Self::Osr2048 => 2048,
+            Self::Osr4096 => 4096,
+            Self::Osr8192 => 8192,
+            Self::Osr16256 => 16256,
+        }
+    }
+}
+
 /// Power mode setting
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -408,6 +480,14 @@ pub enum PgaGain {
     Gain128 = 7,
 }
 
+impl PgaGain {
+    /// The gain multiplier
+    #[must_use]
+    pub const fn factor(self) -> u8 {
+        1 << self as u8
+    }
+}
+
 /// Global chop delay selection
 ///
 /// Delay in modulator clock periods before measurement begins
@@ -468,6 +548,14 @@ pub enum GlobalChopDelay {
     Delay65536 = 15,
 }
 
+impl GlobalChopDelay {
+    /// The number of modulator clock cycles of the delay
+    #[must_use]
+    pub const fn modulator_cycles(self) -> u32 {
+        2 << self as u32
+    }
+}
+
 /// Current-detect channel selection
 /// Channels required to trigger current-detect
 #[derive(Default, Debug, Clone, Copy, PartialEq, Eq, IntoPrimitive, TryFromPrimitive)]
@@ -615,7 +703,7 @@ pub enum DcBlock {
 #[cfg_attr(test, derive(Sequence))]
 #[repr(u8)]
 pub enum ChannelMux {
-    /// AINxP and AINxN
+    /// `AINxP` and `AINxN`
     ///
     /// This is the default channel mux setting
     #[default]
@@ -654,6 +742,10 @@ impl Global for Id {
     fn to_be_bytes(self) -> [u8; 2] {
         [self.channel_count, 0]
     }
+
+    fn read_back_mask(_channel_count: usize) -> [u8; 2] {
+        [0x00, 0x00]
+    }
 }
 
 /// Device `STATUS` register
@@ -769,6 +861,34 @@ impl Global for Status {
                 | u8::from(self.drdy0),
         ]
     }
+
+    fn read_back_mask(_channel_count: usize) -> [u8; 2] {
+        [0x00, 0x00]
+    }
+}
+
+impl Status {
+    /// The channels with new data ready as a bit mask, with channel 0 in the lowest bit
+    #[must_use]
+    pub const fn data_ready(&self) -> u8 {
+        (self.drdy7 as u8) << 7
+            | (self.drdy6 as u8) << 6
+            | (self.drdy5 as u8) << 5
+            | (self.drdy4 as u8) << 4
+            | (self.drdy3 as u8) << 3
+            | (self.drdy2 as u8) << 2
+            | (self.drdy1 as u8) << 1
+            | (self.drdy0 as u8)
+    }
+
+    /// Check if a status word read straight after the previous sample grab shows a missed conversion
+    ///
+    /// A conversion was missed if any channel in `enabled_channels`, as given by
+    /// [`Clock::enabled_channels`], already had new data ready when the previous sample grab was read
+    #[must_use]
+    pub const fn is_overrun(&self, enabled_channels: u8) -> bool {
+        self.data_ready() & enabled_channels != 0
+    }
 }
 
 impl Default for Status {
@@ -857,6 +977,11 @@ impl Global for Mode {
                 | u8::from(self.drdy_ready_state),
         ]
     }
+
+    fn read_back_mask(_channel_count: usize) -> [u8; 2] {
+        // The reset bit can only be cleared
+        [0b0011_1011, 0b0001_1111]
+    }
 }
 
 impl Default for Mode {
@@ -990,6 +1115,42 @@ impl Global for Clock {
                 | u8::from(self.power_mode),
         ]
     }
+
+    fn read_back_mask(channel_count: usize) -> [u8; 2] {
+        let channels = u8::try_from((1u16 << channel_count) - 1).unwrap_or(0xFF);
+        if channel_count > 4 {
+            [channels, 0b1101_1111]
+        } else {
+            [channels, 0b0011_1111]
+        }
+    }
+}
+
+impl Clock {
+    /// The enabled channels as a bit mask, with channel 0 in the lowest bit
+    ///
+    /// This matches the layout of [`Status::data_ready`]
+    #[must_use]
+    pub const fn enabled_channels(&self) -> u8 {
+        (self.channel7_en as u8) << 7
+            | (self.channel6_en as u8) << 6
+            | (self.channel5_en as u8) << 5
+            | (self.channel4_en as u8) << 4
+            | (self.channel3_en as u8) << 3
+            | (self.channel2_en as u8) << 2
+            | (self.channel1_en as u8) << 1
+            | (self.channel0_en as u8)
+    }
+
+    /// The number of modulator clock cycles per conversion, taking turbo mode into account
+    #[must_use]
+    pub const fn modulator_cycles_per_conversion(&self) -> u16 {
+        if self.turbo_mode {
+            64
+        } else {
+            self.oversampling_ratio.ratio()
+        }
+    }
 }
 
 impl Default for Clock {
@@ -1052,6 +1213,14 @@ impl Global for Gain1 {
             u8::from(self.pga_gain0) | u8::from(self.pga_gain1) << 4,
         ]
     }
+
+    fn read_back_mask(channel_count: usize) -> [u8; 2] {
+        match channel_count {
+            2 => [0x00, 0x77],
+            3 => [0x07, 0x77],
+            _ => [0x77, 0x77],
+        }
+    }
 }
 
 /// Device `GAIN2` register
@@ -1096,6 +1265,14 @@ impl Global for Gain2 {
             u8::from(self.pga_gain4) | u8::from(self.pga_gain5) << 4,
         ]
     }
+
+    fn read_back_mask(channel_count: usize) -> [u8; 2] {
+        match channel_count {
+            6 => [0x00, 0x77],
+            8 => [0x77, 0x77],
+            _ => [0x00, 0x00],
+        }
+    }
 }
 
 /// Device `CFG` register
@@ -1146,6 +1323,10 @@ impl Global for Config {
                 | u8::from(self.current_detect_enable),
         ]
     }
+
+    fn read_back_mask(_channel_count: usize) -> [u8; 2] {
+        [0x1F, 0xFF]
+    }
 }
 
 /// Device `THRSHLD_MSB` and `THRSHLD_LSB` registers
@@ -1230,6 +1411,10 @@ impl Global for ThresholdLsb {
     fn to_be_bytes(self) -> [u8; 2] {
         self.bytes
     }
+
+    fn read_back_mask(_channel_count: usize) -> [u8; 2] {
+        [0xFF, 0x0F]
+    }
 }
 
 /// Device `CHx_CFG` register
@@ -1266,6 +1451,10 @@ impl ChannelSpecific for ChannelConfig {
             phase[1] << 6 | u8::from(self.dc_block_disable) << 2 | u8::from(self.mux),
         ]
     }
+
+    fn read_back_mask(_channel_count: usize) -> [u8; 2] {
+        [0xFF, 0xC7]
+    }
 }
 
 /// Device `CHx_OCAL_MSB` and `CHx_OCAL_LSB` registers
@@ -1346,6 +1535,10 @@ impl ChannelSpecific for ChannelOffsetCalLsb {
     fn to_be_bytes(self) -> [u8; 2] {
         self.bytes
     }
+
+    fn read_back_mask(_channel_count: usize) -> [u8; 2] {
+        [0xFF, 0x00]
+    }
 }
 
 /// Device `CHx_GCAL_MSB` and `CHx_GCAL_LSB` registers
@@ -1446,6 +1639,10 @@ impl ChannelSpecific for ChannelGainCalLsb {
     fn to_be_bytes(self) -> [u8; 2] {
         self.bytes
     }
+
+    fn read_back_mask(_channel_count: usize) -> [u8; 2] {
+        [0xFF, 0x00]
+    }
 }
 
 /// Device `REGMAP_CRC` register
@@ -1470,6 +1667,10 @@ impl Global for RegistryMapCrc {
     fn to_be_bytes(self) -> [u8; 2] {
         self.crc.to_be_bytes()
     }
+
+    fn read_back_mask(_channel_count: usize) -> [u8; 2] {
+        [0x00, 0x00]
+    }
 }
 
 #[cfg(test)]
@@ -1478,6 +1679,17 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn address_round_trip() {
+        for address in 0..0x40 {
+            if let Some(addr) = Address::from_address(address) {
+                assert_eq!(addr.address(), address);
+            } else {
+                assert!((0x31..0x3E).contains(&address) || address == 0x3F);
+            }
+        }
+    }
+
     #[test]
     fn id_decode() {
         for (word, channel_count) in [
@@ -1564,6 +1776,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn clock_read_back_mask() {
+        assert_eq!(Clock::read_back_mask(2), [0x03, 0x3F]);
+        assert_eq!(Clock::read_back_mask(4), [0x0F, 0x3F]);
+        assert_eq!(Clock::read_back_mask(6), [0x3F, 0xDF]);
+        assert_eq!(Clock::read_back_mask(8), [0xFF, 0xDF]);
+    }
+
+    #[test]
+    fn global_chop_delay_cycles() {
+        assert_eq!(GlobalChopDelay::Delay2.modulator_cycles(), 2);
+        assert_eq!(GlobalChopDelay::default().modulator_cycles(), 16);
+        assert_eq!(GlobalChopDelay::Delay65536.modulator_cycles(), 65536);
+    }
+
+    #[test]
+    fn clock_modulator_cycles() {
+        assert_eq!(Clock::default().modulator_cycles_per_conversion(), 1024);
+
+        for oversampling_ratio in enum_iterator::all::<OversamplingRatio>() {
+            let clock = Clock {
+                oversampling_ratio,
+                ..Clock::default()
+            };
+            assert_eq!(
+                clock.modulator_cycles_per_conversion(),
+                oversampling_ratio.ratio()
+            );
+
+            let turbo = Clock {
+                turbo_mode: true,
+                ..clock
+            };
+            assert_eq!(turbo.modulator_cycles_per_conversion(), 64);
+        }
+    }
+
     #[test]
     fn gain1_default() {
         assert_eq!(Gain1::default().to_be_bytes(), [0x00, 0x00]);
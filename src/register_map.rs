@@ -0,0 +1,504 @@
+//! A host-side image of the device register map
+
+use crc::{Crc, CRC_16_CMS, CRC_16_IBM_3740};
+
+use crate::delay::Delay;
+use crate::driver::{Driver, SampleSink};
+use crate::profile::{CalibrationProfile, ChannelCalibration};
+use crate::register::{
+    Address, Channel, ChannelConfig, ChannelGainCalLsb, ChannelGainCalMsb, ChannelOffsetCalLsb,
+    ChannelOffsetCalMsb, ChannelSpecific, Clock, Config, CrcType, Gain1, Gain2, Global, Mode,
+    Threshold, ThresholdLsb, ThresholdMsb,
+};
+use crate::spi::Transfer;
+use crate::Error;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The number of global registers covered by the register map CRC, from `MODE` to `THRSHLD_LSB`
+const GLOBAL_REGISTERS: usize = 7;
+
+/// The number of registers for each channel
+const CHANNEL_REGISTERS: usize = 5;
+
+/// An image of every writable register in the device
+///
+/// This is the set of registers covered by the device's register map CRC,
+/// from `MODE` to the last channel-specific register of the model.
+/// The default value is the register map after a reset
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RegisterMap<const CHANNELS: usize> {
+    /// `MODE` register
+    pub mode: Mode,
+
+    /// `CLOCK` register
+    pub clock: Clock,
+
+    /// `GAIN1` register
+    pub gain1: Gain1,
+
+    /// `GAIN2` register
+    ///
+    /// This always reads zero on devices with 4 or fewer channels
+    pub gain2: Gain2,
+
+    /// `CFG` register
+    pub config: Config,
+
+    /// `THRSHLD_MSB` and `THRSHLD_LSB` registers
+    pub threshold: Threshold,
+
+    /// Channel-specific registers of each channel
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_array"))]
+    pub channels: [ChannelCalibration; CHANNELS],
+}
+
+impl<const CHANNELS: usize> RegisterMap<CHANNELS> {
+    /// Read the register map from the device
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if communication with the device failed
+    pub fn read<S, W, P, D>(
+        driver: &mut Driver<S, W, CHANNELS, P, D>,
+        sink: &mut impl SampleSink<CHANNELS>,
+    ) -> Result<Self, Error>
+    where
+        S: Transfer<W>,
+        W: Copy,
+        D: Delay,
+    {
+        let block = driver.read_registers(Address::Mode, Self::register_count(), sink)?;
+
+        let mut map = Self::default();
+        for read in block.iter() {
+            map.set(read.address, read.data);
+        }
+
+        Ok(map)
+    }
+
+    /// Get the value of the register at `address`, MSB first
+    ///
+    /// Returns `None` if the register is not part of the register map,
+    /// or belongs to a channel the device does not have
+    #[must_use]
+    #[allow(clippy::similar_names)]
+    pub fn get(&self, address: Address) -> Option<[u8; 2]> {
+        let (threshold_msb, threshold_lsb) = self.threshold.into_parts();
+
+        let word = match address {
+            Address::Mode => self.mode.to_be_bytes(),
+            Address::Clock => self.clock.to_be_bytes(),
+            Address::Gain1 => self.gain1.to_be_bytes(),
+            Address::Gain2 => self.gain2.to_be_bytes(),
+            Address::Config => self.config.to_be_bytes(),
+            Address::ThresholdMsb => threshold_msb.to_be_bytes(),
+            Address::ThresholdLsb => threshold_lsb.to_be_bytes(),
+            Address::ChannelConfig(channel) => self.channel(channel)?.to_words()[0],
+            Address::ChannelOffsetCalMsb(channel) => self.channel(channel)?.to_words()[1],
+            Address::ChannelOffsetCalLsb(channel) => self.channel(channel)?.to_words()[2],
+            Address::ChannelGainCalMsb(channel) => self.channel(channel)?.to_words()[3],
+            Address::ChannelGainCalLsb(channel) => self.channel(channel)?.to_words()[4],
+            Address::Id | Address::Status | Address::RegisterMapCrc => return None,
+        };
+
+        Some(word)
+    }
+
+    /// Set the value of the register at `address`, MSB first
+    ///
+    /// Returns `false` if the register is not part of the register map,
+    /// or belongs to a channel the device does not have
+    #[allow(clippy::similar_names)]
+    pub fn set(&mut self, address: Address, word: [u8; 2]) -> bool {
+        let (threshold_msb, threshold_lsb) = self.threshold.into_parts();
+
+        match address {
+            Address::Mode => self.mode = Mode::from_be_bytes(word),
+            Address::Clock => self.clock = Clock::from_be_bytes(word),
+            Address::Gain1 => self.gain1 = Gain1::from_be_bytes(word),
+            Address::Gain2 => self.gain2 = Gain2::from_be_bytes(word),
+            Address::Config => self.config = Config::from_be_bytes(word),
+            Address::ThresholdMsb => {
+                self.threshold =
+                    Threshold::from_parts(ThresholdMsb::from_be_bytes(word), threshold_lsb);
+            }
+            Address::ThresholdLsb => {
+                self.threshold =
+                    Threshold::from_parts(threshold_msb, ThresholdLsb::from_be_bytes(word));
+            }
+            Address::ChannelConfig(channel) => return self.set_channel_word(channel, 0, word),
+            Address::ChannelOffsetCalMsb(channel) => {
+                return self.set_channel_word(channel, 1, word)
+            }
+            Address::ChannelOffsetCalLsb(channel) => {
+                return self.set_channel_word(channel, 2, word)
+            }
+            Address::ChannelGainCalMsb(channel) => return self.set_channel_word(channel, 3, word),
+            Address::ChannelGainCalLsb(channel) => return self.set_channel_word(channel, 4, word),
+            Address::Id | Address::Status | Address::RegisterMapCrc => return false,
+        }
+
+        true
+    }
+
+    /// Iterate over the address of each register in the register map, in address order
+    pub fn addresses() -> impl Iterator<Item = Address> {
+        let first = Address::Mode.address();
+
+        (first..first + Self::register_count()).filter_map(Address::from_address)
+    }
+
+    /// The bits of the register at `address` which read back on a device with `CHANNELS` channels
+    ///
+    /// This is the register's read-back mask, plus the `MODE` reset bit,
+    /// which reads back but can only be cleared
+    #[must_use]
+    pub fn read_back_mask(address: Address) -> [u8; 2] {
+        match address {
+            Address::Mode => {
+                let [msb, lsb] = Mode::read_back_mask(CHANNELS);
+                [msb | 0b0000_0100, lsb]
+            }
+            Address::Clock => Clock::read_back_mask(CHANNELS),
+            Address::Gain1 => Gain1::read_back_mask(CHANNELS),
+            Address::Gain2 => Gain2::read_back_mask(CHANNELS),
+            Address::Config => Config::read_back_mask(CHANNELS),
+            Address::ThresholdMsb => ThresholdMsb::read_back_mask(CHANNELS),
+            Address::ThresholdLsb => ThresholdLsb::read_back_mask(CHANNELS),
+            Address::ChannelConfig(_) => ChannelConfig::read_back_mask(CHANNELS),
+            Address::ChannelOffsetCalMsb(_) => ChannelOffsetCalMsb::read_back_mask(CHANNELS),
+            Address::ChannelOffsetCalLsb(_) => ChannelOffsetCalLsb::read_back_mask(CHANNELS),
+            Address::ChannelGainCalMsb(_) => ChannelGainCalMsb::read_back_mask(CHANNELS),
+            Address::ChannelGainCalLsb(_) => ChannelGainCalLsb::read_back_mask(CHANNELS),
+            Address::Id | Address::Status | Address::RegisterMapCrc => [0x00, 0x00],
+        }
+    }
+
+    /// This register map as it reads back from the device, with every bit masked by [`read_back_mask`](Self::read_back_mask)
+    ///
+    /// For example the reserved high bits of `CLOCK`, and `GAIN2` on devices with 4 or fewer channels, read zero
+    #[must_use]
+    pub fn masked(&self) -> Self {
+        let mut map = *self;
+        for (address, word) in Self::addresses().zip(self.words()) {
+            map.set(address, word);
+        }
+
+        map
+    }
+
+    /// Iterate over the value of each register in address order, as it is read from the device
+    ///
+    /// Each word is masked by [`read_back_mask`](Self::read_back_mask)
+    pub fn words(&self) -> impl Iterator<Item = [u8; 2]> + '_ {
+        Self::addresses().filter_map(|address| {
+            let [msb, lsb] = self.get(address)?;
+            let mask = Self::read_back_mask(address);
+            Some([msb & mask[0], lsb & mask[1]])
+        })
+    }
+
+    /// Compute the expected value of the `REGMAP_CRC` register for this register map
+    ///
+    /// The CRC covers every register from `MODE` to the last channel-specific register, MSB first,
+    /// using the CRC type selected in [`Mode::crc_type`].
+    /// With [`Mode::reg_crc_enable`] set, the device reports `Status::reg_map_crc_err` when its
+    /// registers no longer match this CRC
+    #[must_use]
+    pub fn crc(&self) -> u16 {
+        let crc = match self.mode.crc_type {
+            CrcType::Ccitt => Crc::<u16>::new(&CRC_16_IBM_3740),
+            CrcType::Ansi => Crc::<u16>::new(&CRC_16_CMS),
+        };
+
+        let mut digest = crc.digest();
+        for word in self.words() {
+            digest.update(&word);
+        }
+
+        digest.finalize()
+    }
+
+    /// Get the calibration registers of this register map
+    #[must_use]
+    pub const fn profile(&self) -> CalibrationProfile<CHANNELS> {
+        CalibrationProfile {
+            channels: self.channels,
+            gain1: self.gain1,
+            gain2: self.gain2,
+            threshold: self.threshold,
+        }
+    }
+
+    /// Replace the calibration registers of this register map with `profile`
+    pub const fn set_profile(&mut self, profile: &CalibrationProfile<CHANNELS>) {
+        self.channels = profile.channels;
+        self.gain1 = profile.gain1;
+        self.gain2 = profile.gain2;
+        self.threshold = profile.threshold;
+    }
+
+    fn channel(&self, channel: Channel) -> Option<&ChannelCalibration> {
+        self.channels.get(usize::from(u8::from(channel)))
+    }
+
+    fn set_channel_word(&mut self, channel: Channel, idx: usize, word: [u8; 2]) -> bool {
+        let Some(registers) = self.channels.get_mut(usize::from(u8::from(channel))) else {
+            return false;
+        };
+
+        let mut words = registers.to_words();
+        words[idx] = word;
+        *registers = ChannelCalibration::from_words(&words);
+
+        true
+    }
+
+    /// The number of registers in the register map
+    #[allow(clippy::cast_possible_truncation)]
+    const fn register_count() -> u8 {
+        (GLOBAL_REGISTERS + CHANNEL_REGISTERS * CHANNELS) as u8
+    }
+}
+
+/// A set of register addresses
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AddressSet {
+    bits: u64,
+}
+
+impl AddressSet {
+    /// Create an empty set
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { bits: 0 }
+    }
+
+    /// Add `address` to the set
+    pub const fn insert(&mut self, address: Address) {
+        self.bits |= 1 << address.address();
+    }
+
+    /// Check if `address` is in the set
+    #[must_use]
+    pub const fn contains(&self, address: Address) -> bool {
+        self.bits & (1 << address.address()) != 0
+    }
+
+    /// The number of addresses in the set
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    /// Check if the set is empty
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Iterate over the addresses in the set, in address order
+    pub fn iter(&self) -> impl Iterator<Item = Address> + '_ {
+        (0..64_u8)
+            .filter(|address| self.bits & (1 << address) != 0)
+            .filter_map(Address::from_address)
+    }
+}
+
+impl FromIterator<Address> for AddressSet {
+    fn from_iter<T: IntoIterator<Item = Address>>(iter: T) -> Self {
+        let mut set = Self::new();
+        for address in iter {
+            set.insert(address);
+        }
+
+        set
+    }
+}
+
+impl<const CHANNELS: usize> Default for RegisterMap<CHANNELS> {
+    fn default() -> Self {
+        Self {
+            mode: Mode::default(),
+            clock: Clock::default(),
+            gain1: Gain1::default(),
+            gain2: Gain2::default(),
+            config: Config::default(),
+            threshold: Threshold::default(),
+            channels: [ChannelCalibration::default(); CHANNELS],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::driver::Discard;
+    use crate::int::i24;
+    use crate::register::{ChannelOffsetCal, PgaGain};
+    use crate::spi::replay::{frame, open};
+
+    fn map() -> RegisterMap<4> {
+        let mut map = RegisterMap::default();
+        map.gain1.pga_gain2 = PgaGain::Gain32;
+        map.gain2.pga_gain4 = PgaGain::Gain2;
+        map.channels[3].offset_cal = ChannelOffsetCal {
+            offset: i24::try_new(-5).unwrap(),
+        };
+
+        map
+    }
+
+    #[test]
+    fn words() {
+        let map = map();
+        let words: Vec<[u8; 2]> = map.words().collect();
+
+        // MODE to CH3_GCAL_LSB
+        assert_eq!(words.len(), 0x1C - 0x02 + 1);
+        assert_eq!(words[0], Mode::default().to_be_bytes());
+        assert_eq!(words[2], [0x05, 0x00]);
+        // GAIN2 reads zero on an ADS131M04
+        assert_eq!(words[3], [0x00, 0x00]);
+        assert_eq!(
+            words[0x19 - 0x02..=0x1A - 0x02],
+            [[0xFF, 0xFF], [0xFB, 0x00]]
+        );
+        assert_eq!(
+            words[0x1C - 0x02],
+            map.get(Address::ChannelGainCalLsb(Channel::Three)).unwrap()
+        );
+
+        assert_eq!(map.get(Address::ChannelConfig(Channel::Four)), None);
+        assert_eq!(map.get(Address::Status), None);
+    }
+
+    #[test]
+    fn set() {
+        let map = map();
+        let mut copy = RegisterMap::default();
+        for address in RegisterMap::<4>::addresses() {
+            assert!(copy.set(address, map.get(address).unwrap()));
+        }
+        assert_eq!(copy, map);
+
+        assert!(copy.set(Address::ThresholdLsb, [0x78, 0x00]));
+        assert_eq!(copy.threshold.current_detect_threshold.get(), 0x78);
+        assert!(!copy.set(Address::ChannelConfig(Channel::Four), [0, 0]));
+        assert!(!copy.set(Address::Id, [0, 0]));
+    }
+
+    #[test]
+    fn address_set() {
+        let mut set = AddressSet::new();
+        assert!(set.is_empty());
+
+        set.insert(Address::ChannelGainCalLsb(Channel::Seven));
+        set.insert(Address::Mode);
+        set.insert(Address::Mode);
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(Address::Mode));
+        assert!(!set.contains(Address::Clock));
+
+        let addresses: Vec<Address> = set.iter().collect();
+        assert_eq!(
+            addresses,
+            [Address::Mode, Address::ChannelGainCalLsb(Channel::Seven)]
+        );
+        assert_eq!(addresses.into_iter().collect::<AddressSet>(), set);
+    }
+
+    #[test]
+    fn crc() {
+        let mut map = map();
+        let before = map.crc();
+        map.channels[0].gain_cal.gain = crate::int::u24::new_clamped(0x7F_FFFF);
+        assert_ne!(map.crc(), before);
+
+        // Bits which do not read back are not covered
+        let before = map.crc();
+        map.gain2.pga_gain4 = PgaGain::Gain128;
+        assert_eq!(map.crc(), before);
+    }
+
+    #[test]
+    fn masked() {
+        let map = RegisterMap::<4>::default();
+        assert_eq!(map.get(Address::Clock), Some([0xFF, 0x0E]));
+
+        let masked = map.masked();
+        assert_eq!(masked.get(Address::Clock), Some([0x0F, 0x0E]));
+        assert_eq!(masked.mode, map.mode);
+        assert_eq!(masked.crc(), map.crc());
+        assert_eq!(masked.masked(), masked);
+    }
+
+    #[test]
+    fn crc_reset_default() {
+        // ADS131M04 register image after reset, from the register map in the datasheet
+        const RESET: [[u8; 2]; 27] = [
+            [0x05, 0x10], // MODE
+            [0x0F, 0x0E], // CLOCK
+            [0x00, 0x00], // GAIN1
+            [0x00, 0x00], // GAIN2, reserved
+            [0x06, 0x00], // CFG
+            [0x00, 0x00], // THRSHLD_MSB
+            [0x00, 0x00], // THRSHLD_LSB
+            [0x00, 0x00], // CH0_CFG
+            [0x00, 0x00],
+            [0x00, 0x00],
+            [0x80, 0x00], // CH0_GCAL_MSB
+            [0x00, 0x00],
+            [0x00, 0x00], // CH1_CFG
+            [0x00, 0x00],
+            [0x00, 0x00],
+            [0x80, 0x00], // CH1_GCAL_MSB
+            [0x00, 0x00],
+            [0x00, 0x00], // CH2_CFG
+            [0x00, 0x00],
+            [0x00, 0x00],
+            [0x80, 0x00], // CH2_GCAL_MSB
+            [0x00, 0x00],
+            [0x00, 0x00], // CH3_CFG
+            [0x00, 0x00],
+            [0x00, 0x00],
+            [0x80, 0x00], // CH3_GCAL_MSB
+            [0x00, 0x00],
+        ];
+
+        let mut map = RegisterMap::<4>::default();
+        assert_eq!(map.words().collect::<Vec<_>>(), RESET);
+        assert_eq!(map.crc(), 0x1406);
+
+        // CRC_TYPE is part of MODE, so it changes the image as well as the polynomial
+        map.mode.crc_type = CrcType::Ansi;
+        assert_eq!(map.words().next(), Some([0x0D, 0x10]));
+        assert_eq!(map.crc(), 0xFEFF);
+    }
+
+    #[test]
+    fn read() {
+        let map = map();
+        let mut words = Vec::from([[0xE1, 0x1A]]);
+        words.extend(map.words());
+
+        let mut driver = open(&[
+            frame(&[[0xFF, 0x24], [0, 0], [0, 0], [0, 0], [0, 0]]),
+            frame(&words),
+        ]);
+
+        let read = RegisterMap::read(&mut driver, &mut Discard).unwrap();
+        assert_eq!(read.crc(), map.crc());
+        assert_eq!(read.channels, map.channels);
+        assert_eq!(read.gain1, map.gain1);
+        assert!(driver.into_inner().release().is_done());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::int::i24;
+use crate::scaling::{ChannelScaling, ChannelTransforms};
 
 #[cfg(feature = "serde")]
 use serde::de::{Error, Visitor};
@@ -63,6 +64,36 @@ impl<const CHANNELS: usize> SampleGrab<CHANNELS> {
 
         values
     }
+
+    /// Convert the sample data into the voltage across each channel's inputs
+    #[must_use]
+    pub fn into_volts(self, scaling: &ChannelScaling<CHANNELS>) -> [f64; CHANNELS] {
+        let mut values = [0.0; CHANNELS];
+        for (idx, value) in values.iter_mut().enumerate() {
+            let int = i24::from_be_bytes(self.data[idx]).get();
+            *value = f64::from(int) * scaling.lsb_volts(idx);
+        }
+
+        values
+    }
+
+    /// Convert the sample data into engineering units, using each channel's [`LinearTransform`](crate::scaling::LinearTransform)
+    #[must_use]
+    pub fn into_units(
+        self,
+        scaling: &ChannelScaling<CHANNELS>,
+        transforms: &ChannelTransforms<CHANNELS>,
+    ) -> [f64; CHANNELS] {
+        transforms.apply(self.into_volts(scaling))
+    }
+
+    /// Convert the sample data into the voltage across each channel's inputs,
+    /// using single precision floating point numbers
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn into_volts_f32(self, scaling: &ChannelScaling<CHANNELS>) -> [f32; CHANNELS] {
+        self.into_volts(scaling).map(|volts| volts as f32)
+    }
 }
 
 #[cfg(feature = "serde")]
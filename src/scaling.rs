@@ -0,0 +1,398 @@
+//! Conversion of samples to physical units
+
+use crate::register::{Clock, Gain1, Gain2, PgaGain};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// The voltage of the internal reference
+pub const INTERNAL_REFERENCE_VOLTS: f64 = 1.2;
+
+/// The scaling of the samples from each channel, based on the PGA gain and reference voltage
+///
+/// The full scale range of each channel is ±`reference` / `gain`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ChannelScaling<const CHANNELS: usize> {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_array"))]
+    gains: [PgaGain; CHANNELS],
+    reference_volts: f64,
+}
+
+impl<const CHANNELS: usize> ChannelScaling<CHANNELS> {
+    /// Describe the channel scaling from the gain registers and a reference voltage
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `CHANNELS` is greater than 8
+    #[must_use]
+    pub fn new(gain1: &Gain1, gain2: &Gain2, reference_volts: f64) -> Self {
+        let all_gains = [
+            gain1.pga_gain0,
+            gain1.pga_gain1,
+            gain1.pga_gain2,
+            gain1.pga_gain3,
+            gain2.pga_gain4,
+            gain2.pga_gain5,
+            gain2.pga_gain6,
+            gain2.pga_gain7,
+        ];
+
+        Self {
+            gains: core::array::from_fn(|channel| all_gains[channel]),
+            reference_volts,
+        }
+    }
+
+    /// Describe the channel scaling from the gain registers, using the internal reference
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `CHANNELS` is greater than 8
+    #[must_use]
+    pub fn with_internal_reference(gain1: &Gain1, gain2: &Gain2) -> Self {
+        Self::new(gain1, gain2, INTERNAL_REFERENCE_VOLTS)
+    }
+
+    /// Describe the channel scaling from the gain registers, using the reference selected by `clock`
+    ///
+    /// `external_reference_volts` is only used if the external reference is enabled
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `CHANNELS` is greater than 8
+    #[must_use]
+    pub fn from_registers(
+        clock: &Clock,
+        gain1: &Gain1,
+        gain2: &Gain2,
+        external_reference_volts: f64,
+    ) -> Self {
+        let reference_volts = if clock.external_ref_enable {
+            external_reference_volts
+        } else {
+            INTERNAL_REFERENCE_VOLTS
+        };
+
+        Self::new(gain1, gain2, reference_volts)
+    }
+
+    /// The PGA gain of each channel
+    #[must_use]
+    pub const fn gains(&self) -> &[PgaGain; CHANNELS] {
+        &self.gains
+    }
+
+    /// The reference voltage
+    #[must_use]
+    pub const fn reference_volts(&self) -> f64 {
+        self.reference_volts
+    }
+
+    /// The voltage represented by one code on `channel`
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `channel` is not less than `CHANNELS`
+    #[must_use]
+    pub fn lsb_volts(&self, channel: usize) -> f64 {
+        self.full_scale_volts(channel) / f64::from(1_u32 << 23)
+    }
+
+    /// The largest voltage which can be measured on `channel`
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `channel` is not less than `CHANNELS`
+    #[must_use]
+    pub fn full_scale_volts(&self, channel: usize) -> f64 {
+        self.reference_volts / f64::from(self.gains[channel].factor())
+    }
+}
+
+/// The unit of a converted channel value
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Unit {
+    /// Volts
+    #[default]
+    Volts,
+
+    /// Amps
+    Amps,
+
+    /// Degrees Celsius
+    DegreesCelsius,
+
+    /// No unit
+    Unitless,
+}
+
+/// A linear conversion from the voltage across a channel's inputs to an engineering unit
+///
+/// The converted value is `volts * scale + offset`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LinearTransform {
+    /// Multiplier applied to the input voltage
+    pub scale: f64,
+
+    /// Offset added after scaling
+    pub offset: f64,
+
+    /// Unit of the converted value
+    pub unit: Unit,
+}
+
+impl Default for LinearTransform {
+    fn default() -> Self {
+        Self::volts()
+    }
+}
+
+impl LinearTransform {
+    /// Leave the input voltage unchanged
+    #[must_use]
+    pub const fn volts() -> Self {
+        Self {
+            scale: 1.0,
+            offset: 0.0,
+            unit: Unit::Volts,
+        }
+    }
+
+    /// Measure the current through a shunt resistor across the channel inputs
+    #[must_use]
+    pub fn shunt(shunt_ohms: f64) -> Self {
+        Self {
+            scale: 1.0 / shunt_ohms,
+            offset: 0.0,
+            unit: Unit::Amps,
+        }
+    }
+
+    /// Measure the voltage at the top of a resistor divider, with the channel across the bottom resistor
+    #[must_use]
+    pub fn divider(top_ohms: f64, bottom_ohms: f64) -> Self {
+        Self {
+            scale: (top_ohms + bottom_ohms) / bottom_ohms,
+            offset: 0.0,
+            unit: Unit::Volts,
+        }
+    }
+
+    /// Measure the primary current of a current transformer, with the channel across the burden resistor
+    ///
+    /// `turns_ratio` is the number of secondary turns per primary turn
+    #[must_use]
+    pub fn current_transformer(turns_ratio: f64, burden_ohms: f64) -> Self {
+        Self {
+            scale: turns_ratio / burden_ohms,
+            offset: 0.0,
+            unit: Unit::Amps,
+        }
+    }
+
+    /// Measure the temperature of a linear analog temperature sensor
+    #[must_use]
+    pub fn temperature_sensor(volts_per_degree: f64, volts_at_zero: f64) -> Self {
+        Self {
+            scale: 1.0 / volts_per_degree,
+            offset: -volts_at_zero / volts_per_degree,
+            unit: Unit::DegreesCelsius,
+        }
+    }
+
+    /// Convert a voltage
+    #[must_use]
+    pub fn apply(&self, volts: f64) -> f64 {
+        volts * self.scale + self.offset
+    }
+}
+
+/// The [`LinearTransform`] for each channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ChannelTransforms<const CHANNELS: usize> {
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_array"))]
+    transforms: [LinearTransform; CHANNELS],
+}
+
+impl<const CHANNELS: usize> Default for ChannelTransforms<CHANNELS> {
+    fn default() -> Self {
+        Self::new([LinearTransform::volts(); CHANNELS])
+    }
+}
+
+impl<const CHANNELS: usize> ChannelTransforms<CHANNELS> {
+    /// Use a transform for each channel
+    #[must_use]
+    pub const fn new(transforms: [LinearTransform; CHANNELS]) -> Self {
+        Self { transforms }
+    }
+
+    /// The transform of each channel
+    #[must_use]
+    pub const fn transforms(&self) -> &[LinearTransform; CHANNELS] {
+        &self.transforms
+    }
+
+    /// Replace the transform of `channel`
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `channel` is not less than `CHANNELS`
+    pub const fn set(&mut self, channel: usize, transform: LinearTransform) {
+        self.transforms[channel] = transform;
+    }
+
+    /// Convert the voltage of every channel
+    #[must_use]
+    pub fn apply(&self, volts: [f64; CHANNELS]) -> [f64; CHANNELS] {
+        let mut values = volts;
+        for (value, transform) in values.iter_mut().zip(&self.transforms) {
+            *value = transform.apply(*value);
+        }
+
+        values
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interface::SampleGrab;
+    use float_cmp::assert_approx_eq;
+
+    #[test]
+    fn every_gain() {
+        for gain in enum_iterator::all::<PgaGain>() {
+            let scaling = ChannelScaling::<1>::with_internal_reference(
+                &Gain1 {
+                    pga_gain0: gain,
+                    ..Gain1::default()
+                },
+                &Gain2::default(),
+            );
+            let full_scale = 1.2 / f64::from(gain.factor());
+            assert_approx_eq!(f64, scaling.full_scale_volts(0), full_scale);
+
+            let volts = SampleGrab {
+                data: [[0x7F, 0xFF, 0xFF]],
+            }
+            .into_volts(&scaling);
+            assert_approx_eq!(
+                f64,
+                volts[0],
+                full_scale - scaling.lsb_volts(0),
+                epsilon = 1e-12
+            );
+
+            let volts = SampleGrab {
+                data: [[0x80, 0x00, 0x00]],
+            }
+            .into_volts_f32(&scaling);
+            #[allow(clippy::cast_possible_truncation)]
+            let expected = -full_scale as f32;
+            assert_approx_eq!(f32, volts[0], expected);
+        }
+
+        assert_eq!(PgaGain::Gain1.factor(), 1);
+        assert_eq!(PgaGain::Gain128.factor(), 128);
+    }
+
+    #[test]
+    fn channel_gains() {
+        let gain1 = Gain1 {
+            pga_gain1: PgaGain::Gain2,
+            ..Gain1::default()
+        };
+        let gain2 = Gain2 {
+            pga_gain7: PgaGain::Gain64,
+            ..Gain2::default()
+        };
+        let scaling = ChannelScaling::<8>::new(&gain1, &gain2, 2.5);
+        assert_eq!(scaling.gains()[0], PgaGain::Gain1);
+        assert_eq!(scaling.gains()[1], PgaGain::Gain2);
+        assert_eq!(scaling.gains()[7], PgaGain::Gain64);
+        assert_approx_eq!(f64, scaling.full_scale_volts(7), 2.5 / 64.0);
+
+        let volts = SampleGrab {
+            data: [
+                [0x00, 0x00, 0x01],
+                [0xFF, 0xFF, 0xFF],
+                [0; 3],
+                [0; 3],
+                [0; 3],
+                [0; 3],
+                [0; 3],
+                [0x40, 0, 0],
+            ],
+        }
+        .into_volts(&scaling);
+        assert_approx_eq!(f64, volts[0], 2.5 / 8_388_608.0);
+        assert_approx_eq!(f64, volts[1], -1.25 / 8_388_608.0);
+        assert_approx_eq!(f64, volts[7], 2.5 / 64.0 / 2.0);
+    }
+
+    #[test]
+    fn reference_selection() {
+        let internal = ChannelScaling::<4>::from_registers(
+            &Clock::default(),
+            &Gain1::default(),
+            &Gain2::default(),
+            2.5,
+        );
+        assert_approx_eq!(f64, internal.reference_volts(), INTERNAL_REFERENCE_VOLTS);
+
+        let external = ChannelScaling::<4>::from_registers(
+            &Clock {
+                external_ref_enable: true,
+                ..Clock::default()
+            },
+            &Gain1::default(),
+            &Gain2::default(),
+            2.5,
+        );
+        assert_approx_eq!(f64, external.reference_volts(), 2.5);
+    }
+
+    #[test]
+    fn transforms() {
+        assert_approx_eq!(f64, LinearTransform::volts().apply(0.5), 0.5);
+        assert_approx_eq!(f64, LinearTransform::shunt(0.01).apply(0.05), 5.0);
+        assert_approx_eq!(
+            f64,
+            LinearTransform::divider(999_000.0, 1_000.0).apply(0.23),
+            230.0,
+            epsilon = 1e-9
+        );
+        assert_approx_eq!(
+            f64,
+            LinearTransform::current_transformer(2000.0, 20.0).apply(0.1),
+            10.0
+        );
+
+        let sensor = LinearTransform::temperature_sensor(0.01, 0.5);
+        assert_eq!(sensor.unit, Unit::DegreesCelsius);
+        assert_approx_eq!(f64, sensor.apply(0.75), 25.0, epsilon = 1e-9);
+        assert_approx_eq!(f64, sensor.apply(0.4), -10.0, epsilon = 1e-9);
+    }
+
+    #[test]
+    fn sample_to_units() {
+        let scaling =
+            ChannelScaling::<2>::with_internal_reference(&Gain1::default(), &Gain2::default());
+        let mut transforms = ChannelTransforms::default();
+        transforms.set(1, LinearTransform::shunt(0.1));
+        assert_eq!(transforms.transforms()[0], LinearTransform::volts());
+        assert_eq!(transforms.transforms()[1].unit, Unit::Amps);
+
+        let values = SampleGrab {
+            data: [[0x40, 0x00, 0x00], [0x20, 0x00, 0x00]],
+        }
+        .into_units(&scaling, &transforms);
+        assert_approx_eq!(f64, values[0], 0.6);
+        assert_approx_eq!(f64, values[1], 3.0);
+    }
+}
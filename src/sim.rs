@@ -0,0 +1,662 @@
+//! A behavioural model of the device, for testing without hardware
+//!
+//! [`Simulator`] implements [`Transfer`], so it can be opened like a real device
+//! with [`Ads131m`](crate::interface::Ads131m). Each transfer is one SPI frame,
+//! and the response to each command is returned in the following frame.
+//!
+//! The model covers the SPI protocol and the register file. Conversions are produced
+//! instantly, one per frame, with an optional input offset, the PGA gain, and the offset and
+//! gain calibration applied.
+//! The phase delay, DC block filter, global-chop and current-detect are not modelled.
+
+use crc::{Crc, CRC_16_CMS, CRC_16_IBM_3740};
+
+use crate::interface::MAX_READ_LEN;
+use crate::register::{Address, Channel, ChannelMux, CrcType, Global, Mode, Status, WordLength};
+use crate::register_map::RegisterMap;
+use crate::spi::Transfer;
+use crate::Error;
+
+/// The code of the positive DC test signal at a gain of 1
+///
+/// This is 160 mV against the 1.2 V internal reference
+pub const TEST_SIGNAL_CODE: i32 = 1_118_481;
+
+/// The largest positive 24-bit code
+const MAX_CODE: i64 = (1 << 23) - 1;
+
+/// The largest negative 24-bit code
+const MIN_CODE: i64 = -(1 << 23);
+
+/// A source of the analog input of each channel
+pub trait Waveform {
+    /// The input of `channel` for the conversion numbered `conversion`, as a 24-bit code at a gain of 1
+    ///
+    /// Conversions are numbered from zero, counting from when the simulator was created
+    fn sample(&mut self, channel: Channel, conversion: u64) -> i32;
+}
+
+impl<F> Waveform for F
+where
+    F: FnMut(Channel, u64) -> i32,
+{
+    fn sample(&mut self, channel: Channel, conversion: u64) -> i32 {
+        self(channel, conversion)
+    }
+}
+
+/// A constant input for each channel
+impl<const CHANNELS: usize> Waveform for [i32; CHANNELS] {
+    fn sample(&mut self, channel: Channel, _conversion: u64) -> i32 {
+        self.get(usize::from(u8::from(channel)))
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+/// A waveform with every input at zero
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Silence;
+
+impl Waveform for Silence {
+    fn sample(&mut self, _channel: Channel, _conversion: u64) -> i32 {
+        0
+    }
+}
+
+/// The response the device will send in the next frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PendingResponse {
+    Reset,
+    Status,
+    Word([u8; 2]),
+    WriteRegisters { addr: u8, count: u8 },
+    ReadRegisters { addr: u8, count: u8 },
+}
+
+/// A simulated device with `CHANNELS` channels
+///
+/// The simulator starts as if it had just been powered on, so the first frame acknowledges a reset
+#[derive(Debug, Clone)]
+pub struct Simulator<const CHANNELS: usize, G = Silence> {
+    registers: RegisterMap<CHANNELS>,
+    waveform: G,
+    input_offsets: [i32; CHANNELS],
+    response: PendingResponse,
+    locked: bool,
+    standby: bool,
+    spi_crc_err: bool,
+    conversion: u64,
+}
+
+impl<const CHANNELS: usize> Simulator<CHANNELS> {
+    /// Create a simulated device with every input at zero
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_waveform(Silence)
+    }
+}
+
+impl<const CHANNELS: usize> Default for Simulator<CHANNELS> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const CHANNELS: usize, G: Waveform> Simulator<CHANNELS, G> {
+    /// Create a simulated device with its inputs driven by `waveform`
+    #[must_use]
+    pub fn with_waveform(waveform: G) -> Self {
+        Self {
+            registers: reset_registers(),
+            waveform,
+            input_offsets: [0; CHANNELS],
+            response: PendingResponse::Reset,
+            locked: false,
+            standby: false,
+            spi_crc_err: false,
+            conversion: 0,
+        }
+    }
+
+    /// Add a fixed offset to the input of each channel, as a 24-bit code at a gain of 1
+    ///
+    /// The offset is added to every input mux selection, like the offset error of a real device
+    #[must_use]
+    pub const fn with_input_offsets(mut self, offsets: [i32; CHANNELS]) -> Self {
+        self.input_offsets = offsets;
+        self
+    }
+
+    /// The current value of each writable register
+    pub const fn registers(&self) -> &RegisterMap<CHANNELS> {
+        &self.registers
+    }
+
+    /// Mutable access to the waveform driving the inputs
+    pub const fn waveform_mut(&mut self) -> &mut G {
+        &mut self.waveform
+    }
+
+    /// Check if the SPI interface is locked
+    #[must_use]
+    pub const fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Check if the device is in standby mode
+    #[must_use]
+    pub const fn is_standby(&self) -> bool {
+        self.standby
+    }
+
+    /// Reset every register, as if the `SYNC/RESET` pin was held low
+    ///
+    /// The next frame acknowledges the reset
+    pub fn hardware_reset(&mut self) {
+        self.reset();
+    }
+
+    fn reset(&mut self) {
+        self.registers = reset_registers();
+        self.response = PendingResponse::Reset;
+        self.locked = false;
+        self.standby = false;
+        self.spi_crc_err = false;
+    }
+
+    /// Build the frame holding the pending response, and return its length
+    fn encode_response(&mut self, buf: &mut [u8]) -> usize {
+        let mode = self.registers.mode;
+        let mut frame = FrameWriter {
+            buf,
+            len: 0,
+            word_len: mode.word_length.byte_count(),
+        };
+
+        let samples = match self.response {
+            PendingResponse::Reset => {
+                frame.push_word([0xFF, 0x20 | channel_count::<CHANNELS>()]);
+                true
+            }
+            PendingResponse::Status => {
+                let status = self.status();
+                frame.push_word(status);
+                true
+            }
+            PendingResponse::Word(word) => {
+                frame.push_word(word);
+                true
+            }
+            PendingResponse::WriteRegisters { addr, count } => {
+                frame.push_word([0x40 | addr >> 1, (addr & 0b1) << 7 | (count - 1)]);
+                true
+            }
+            PendingResponse::ReadRegisters { addr, count: 1 } => {
+                let word = self.read_register(addr);
+                frame.push_word(word);
+                false
+            }
+            PendingResponse::ReadRegisters { addr, count } => {
+                frame.push_word([0xE0 | addr >> 1, (addr & 0b1) << 7 | (count - 1)]);
+                for addr in addr..addr + count {
+                    let word = self.read_register(addr);
+                    frame.push_word(word);
+                }
+                false
+            }
+        };
+
+        if samples {
+            let conversion = self.conversion;
+            for idx in 0..channel_count::<CHANNELS>() {
+                let sample =
+                    Channel::try_from(idx).map_or(0, |channel| self.convert(channel, conversion));
+                frame.push_sample(sample, mode.word_length);
+            }
+
+            if !self.standby {
+                self.conversion += 1;
+            }
+        }
+
+        let crc = crc_for(mode.crc_type).checksum(&frame.buf[..frame.len]);
+        frame.push_word(crc.to_be_bytes());
+
+        frame.len
+    }
+
+    /// Decode and run the command at the start of `frame`
+    fn execute(&mut self, frame: &[u8]) {
+        let mode = self.registers.mode;
+        let word_len = mode.word_length.byte_count();
+        let word = |idx: usize| -> [u8; 2] {
+            let start = idx * word_len;
+            [
+                frame.get(start).copied().unwrap_or(0),
+                frame.get(start + 1).copied().unwrap_or(0),
+            ]
+        };
+
+        let opcode = word(0);
+        let addr = (opcode[0] & 0x1F) << 1 | opcode[1] >> 7;
+        let count = (opcode[1] & 0x7F) + 1;
+        let command_words = if opcode[0] & 0xE0 == 0x60 {
+            1 + usize::from(count)
+        } else {
+            1
+        };
+
+        if mode.spi_crc_enable {
+            let end = (command_words * word_len).min(frame.len());
+            let crc = crc_for(mode.crc_type).checksum(&frame[..end]);
+            if word(command_words) != crc.to_be_bytes() {
+                self.spi_crc_err = true;
+                self.response = PendingResponse::Status;
+                return;
+            }
+        }
+
+        self.response = match opcode {
+            [0x00, 0x11] if !self.locked => {
+                self.reset();
+                PendingResponse::Reset
+            }
+            [0x00, 0x22] if !self.locked => {
+                self.standby = true;
+                PendingResponse::Word(opcode)
+            }
+            [0x00, 0x33] if !self.locked => {
+                self.standby = false;
+                PendingResponse::Word(opcode)
+            }
+            [0x05, 0x55] if !self.locked => {
+                self.locked = true;
+                PendingResponse::Word(opcode)
+            }
+            [0x06, 0x55] => {
+                self.locked = false;
+                PendingResponse::Word(opcode)
+            }
+            [op, _] if op & 0xE0 == 0x60 && !self.locked => {
+                for (offset, addr) in (addr..addr + count).enumerate() {
+                    self.write_register(addr, word(1 + offset));
+                }
+                PendingResponse::WriteRegisters { addr, count }
+            }
+            [op, _] if op & 0xE0 == 0xA0 => PendingResponse::ReadRegisters { addr, count },
+            _ => PendingResponse::Status,
+        };
+    }
+
+    /// The `STATUS` word, which clears the SPI CRC error flag
+    fn status(&mut self) -> [u8; 2] {
+        let mode = self.registers.mode;
+        let enabled = self.registers.clock.to_be_bytes()[0];
+        let drdy = if self.standby { 0 } else { enabled };
+        let status = Status::from_be_bytes([0, drdy & channel_mask::<CHANNELS>()]);
+
+        Status {
+            lock: self.locked,
+            spi_crc_err: core::mem::take(&mut self.spi_crc_err),
+            crc_type: mode.crc_type,
+            reset: mode.reset,
+            word_length: mode.word_length,
+            ..status
+        }
+        .to_be_bytes()
+    }
+
+    /// Read the register at `addr`, with unused addresses reading zero
+    fn read_register(&mut self, addr: u8) -> [u8; 2] {
+        match Address::from_address(addr) {
+            Some(Address::Id) => [0x20 | channel_count::<CHANNELS>(), 0x00],
+            Some(Address::Status) => self.status(),
+            Some(Address::RegisterMapCrc) => self.registers.crc().to_be_bytes(),
+            Some(address) => self.registers.get(address).unwrap_or([0; 2]),
+            None => [0; 2],
+        }
+    }
+
+    /// Write the register at `addr`, ignoring read only and reserved bits
+    fn write_register(&mut self, addr: u8, word: [u8; 2]) {
+        let Some(address) = Address::from_address(addr) else {
+            return;
+        };
+
+        let mask = RegisterMap::<CHANNELS>::read_back_mask(address);
+        let masked = [word[0] & mask[0], word[1] & mask[1]];
+        if address == Address::Mode {
+            // The reset bit can only be cleared
+            let mode = Mode {
+                reset: self.registers.mode.reset && Mode::from_be_bytes(word).reset,
+                ..Mode::from_be_bytes(masked)
+            };
+            self.registers.mode = mode;
+        } else {
+            self.registers.set(address, masked);
+        }
+    }
+
+    /// Convert the input of `channel`, applying the gain and calibration registers
+    fn convert(&mut self, channel: Channel, conversion: u64) -> i32 {
+        let idx = usize::from(u8::from(channel));
+        let clock = self.registers.clock.to_be_bytes();
+        if self.standby || clock[0] >> idx & 0b1 == 0 {
+            return 0;
+        }
+
+        let Some(calibration) = self.registers.channels.get(idx).copied() else {
+            return 0;
+        };
+
+        let input = match calibration.config.mux {
+            ChannelMux::AnalogIn => self.waveform.sample(channel, conversion),
+            ChannelMux::Shorted => 0,
+            ChannelMux::PositiveTest => TEST_SIGNAL_CODE,
+            ChannelMux::NegativeTest => -TEST_SIGNAL_CODE,
+        };
+
+        let gain = match channel {
+            Channel::Zero => self.registers.gain1.pga_gain0,
+            Channel::One => self.registers.gain1.pga_gain1,
+            Channel::Two => self.registers.gain1.pga_gain2,
+            Channel::Three => self.registers.gain1.pga_gain3,
+            Channel::Four => self.registers.gain2.pga_gain4,
+            Channel::Five => self.registers.gain2.pga_gain5,
+            Channel::Six => self.registers.gain2.pga_gain6,
+            Channel::Seven => self.registers.gain2.pga_gain7,
+        };
+
+        let input = i64::from(input) + i64::from(self.input_offsets[idx]);
+        let code =
+            input * i64::from(gain.factor()) - i64::from(calibration.offset_cal.offset.get());
+        let code = (code * i64::from(calibration.gain_cal.gain.get())) >> 23;
+
+        #[allow(clippy::cast_possible_truncation)]
+        let code = code.clamp(MIN_CODE, MAX_CODE) as i32;
+        code
+    }
+}
+
+impl<const CHANNELS: usize, G: Waveform> Transfer<u8> for Simulator<CHANNELS, G> {
+    fn transfer(&mut self, send: &[u8], receive: &mut [u8]) -> Result<(), Error> {
+        let mut frame = [0; MAX_READ_LEN];
+        let len = self.encode_response(&mut frame).min(receive.len());
+        receive[..len].copy_from_slice(&frame[..len]);
+        receive[len..].fill(0);
+
+        self.execute(send);
+
+        Ok(())
+    }
+}
+
+/// Writes words into a frame, packed to the device word length
+struct FrameWriter<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    word_len: usize,
+}
+
+impl FrameWriter<'_> {
+    /// Write a 16-bit word, padded with zeros to the word length
+    fn push_word(&mut self, word: [u8; 2]) {
+        let slot = &mut self.buf[self.len..self.len + self.word_len];
+        slot.fill(0);
+        slot[..2].copy_from_slice(&word);
+        self.len += self.word_len;
+    }
+
+    /// Write a 24-bit sample, packed as `word_length` specifies
+    fn push_sample(&mut self, sample: i32, word_length: WordLength) {
+        let bytes = sample.to_be_bytes();
+        let slot = &mut self.buf[self.len..self.len + self.word_len];
+        match word_length {
+            WordLength::Bits16 => slot.copy_from_slice(&bytes[1..3]),
+            WordLength::Bits24 => slot.copy_from_slice(&bytes[1..4]),
+            WordLength::Bits32Zero => {
+                slot[..3].copy_from_slice(&bytes[1..4]);
+                slot[3] = 0;
+            }
+            WordLength::Bits32Signed => slot.copy_from_slice(&bytes),
+        }
+        self.len += self.word_len;
+    }
+}
+
+/// The register file after a reset
+fn reset_registers<const CHANNELS: usize>() -> RegisterMap<CHANNELS> {
+    RegisterMap::default().masked()
+}
+
+/// The CRC used for communication
+const fn crc_for(crc_type: CrcType) -> Crc<u16> {
+    match crc_type {
+        CrcType::Ccitt => Crc::<u16>::new(&CRC_16_IBM_3740),
+        CrcType::Ansi => Crc::<u16>::new(&CRC_16_CMS),
+    }
+}
+
+/// The channel count, as reported in the reset acknowledgement and `ID` register
+#[allow(clippy::cast_possible_truncation)]
+const fn channel_count<const CHANNELS: usize>() -> u8 {
+    CHANNELS as u8 & 0x0F
+}
+
+/// A bit for each channel the device has
+#[allow(clippy::cast_possible_truncation)]
+const fn channel_mask<const CHANNELS: usize>() -> u8 {
+    ((1_u16 << CHANNELS) - 1) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use super::*;
+    use crate::driver::{Discard, Driver};
+    use crate::int::{i24, u24};
+    use crate::interface::Ads131m;
+    use crate::register::{Clock, Config, Gain1};
+    use crate::register::{PgaGain, RegistryMapCrc};
+
+    fn open<G: Waveform>(sim: Simulator<4, G>) -> Driver<Simulator<4, G>, u8, 4> {
+        Driver::new(Ads131m::open_ads131m04(sim))
+    }
+
+    #[test]
+    fn samples() {
+        let mut driver = open(Simulator::with_waveform([1000, -2000, 3000, -4000]));
+        driver.check_model(&mut Discard).unwrap();
+
+        let gain1 = Gain1 {
+            pga_gain1: PgaGain::Gain4,
+            ..Gain1::default()
+        };
+        driver.write_register(gain1, &mut Discard).unwrap();
+        assert_eq!(
+            driver.read_sample_grab().unwrap().into_i32_array(),
+            [1000, -8000, 3000, -4000]
+        );
+
+        let mut clock = driver.read_register::<Clock>(&mut Discard).unwrap();
+        assert_eq!(clock.to_be_bytes(), [0x0F, 0x0E]);
+        clock.channel2_en = false;
+        driver.write_register(clock, &mut Discard).unwrap();
+        assert_eq!(
+            driver.read_sample_grab().unwrap().into_i32_array(),
+            [1000, -8000, 0, -4000]
+        );
+    }
+
+    #[test]
+    fn conversions_are_numbered() {
+        let mut conversions = Vec::new();
+        let mut driver = open(Simulator::with_waveform(|channel, conversion| {
+            i32::from(u8::from(channel)) * 100 + i32::try_from(conversion).unwrap()
+        }));
+        for _ in 0..3 {
+            conversions.push(
+                driver
+                    .read_frame()
+                    .unwrap()
+                    .sample_grab
+                    .unwrap()
+                    .into_i32_array(),
+            );
+        }
+
+        assert_eq!(
+            conversions,
+            [[0, 100, 200, 300], [1, 101, 201, 301], [2, 102, 202, 302]]
+        );
+    }
+
+    #[test]
+    fn word_lengths_and_crc() {
+        let mut driver = open(Simulator::with_waveform([-0x12_3456, 0x12_3456, 1, -1]));
+
+        for word_length in [
+            WordLength::Bits16,
+            WordLength::Bits24,
+            WordLength::Bits32Zero,
+            WordLength::Bits32Signed,
+        ] {
+            for crc_type in [CrcType::Ccitt, CrcType::Ansi] {
+                let mode = Mode {
+                    word_length,
+                    crc_type,
+                    spi_crc_enable: true,
+                    reset: false,
+                    ..Mode::default()
+                };
+                driver.write_register(mode, &mut Discard).unwrap();
+                assert_eq!(driver.read_register::<Mode>(&mut Discard), Ok(mode));
+
+                let samples = driver.read_sample_grab().unwrap().into_i32_array();
+                if word_length == WordLength::Bits16 {
+                    assert_eq!(samples, [-0x12_3500, 0x12_3400, 0, -0x100]);
+                } else {
+                    assert_eq!(samples, [-0x12_3456, 0x12_3456, 1, -1]);
+                }
+
+                let map = RegisterMap::read(&mut driver, &mut Discard).unwrap();
+                let crc = driver
+                    .read_register::<RegistryMapCrc>(&mut Discard)
+                    .unwrap();
+                assert_eq!(crc.crc, map.crc());
+            }
+        }
+
+        let sim = driver.into_inner().release();
+        assert_eq!(sim.registers().mode.word_length, WordLength::Bits32Signed);
+    }
+
+    #[test]
+    fn input_crc_error() {
+        let mut sim = Simulator::<4>::new();
+        let mut rx = [0; 18];
+
+        // Enable input CRC checking and clear the reset flag
+        sim.transfer(&[0x61, 0x00, 0x00, 0x11, 0x10, 0x00], &mut rx)
+            .unwrap();
+        assert_eq!(rx[..2], [0xFF, 0x24]);
+
+        // A null command with an invalid CRC
+        sim.transfer(&[0x00, 0x00, 0x00, 0xDE, 0xAD, 0x00], &mut rx)
+            .unwrap();
+        assert_eq!(rx[..2], [0x41, 0x00]);
+
+        sim.transfer(&[0x00; 6], &mut rx).unwrap();
+        let status = Status::from_be_bytes([rx[0], rx[1]]);
+        assert!(status.spi_crc_err);
+        assert!(!status.reset);
+        assert!(status.drdy0 && status.drdy3);
+    }
+
+    #[test]
+    fn lock_and_standby() {
+        let mut driver = open(Simulator::with_waveform([5, 6, 7, 8]));
+
+        driver.lock(&mut Discard).unwrap();
+        assert_eq!(
+            driver.write_register(Gain1::default(), &mut Discard),
+            Err(Error::UnexpectedResponse)
+        );
+        assert_eq!(driver.standby(&mut Discard), Err(Error::UnexpectedResponse));
+        assert!(driver.read_register::<Config>(&mut Discard).is_ok());
+        driver.unlock(&mut Discard).unwrap();
+
+        driver.standby(&mut Discard).unwrap();
+        let status = driver.read_frame().unwrap().status.unwrap();
+        assert!(!status.drdy0);
+        assert_eq!(
+            driver.read_sample_grab().unwrap().into_i32_array(),
+            [0, 0, 0, 0]
+        );
+        driver.wakeup(&mut Discard).unwrap();
+        assert_eq!(
+            driver.read_sample_grab().unwrap().into_i32_array(),
+            [5, 6, 7, 8]
+        );
+    }
+
+    #[test]
+    fn calibration_and_test_signal() {
+        let mut registers = reset_registers::<4>();
+        registers.channels[0].offset_cal.offset = i24::new_clamped(200);
+        registers.channels[0].gain_cal.gain = u24::new_clamped(0x40_0000);
+        registers.channels[1].config.mux = ChannelMux::PositiveTest;
+        registers.channels[2].config.mux = ChannelMux::NegativeTest;
+
+        let mut driver = open(Simulator::with_waveform([1000, 0, 0, 0]));
+        for (address, word) in RegisterMap::<4>::addresses().zip(registers.words()) {
+            driver
+                .write_registers(address, &[word], &mut Discard)
+                .unwrap();
+        }
+
+        assert_eq!(
+            driver.read_sample_grab().unwrap().into_i32_array(),
+            [400, TEST_SIGNAL_CODE, -TEST_SIGNAL_CODE, 0]
+        );
+    }
+
+    #[test]
+    fn reset() {
+        let mut driver = open(Simulator::<4>::new());
+        driver
+            .write_register(
+                Config {
+                    global_chop_enable: true,
+                    ..Config::default()
+                },
+                &mut Discard,
+            )
+            .unwrap();
+        driver.reset(&mut Discard).unwrap();
+
+        let sim = driver.into_inner().release();
+        assert_eq!(sim.registers(), &reset_registers());
+        assert!(sim.registers().mode.reset);
+        assert_eq!(sim.registers().gain2.to_be_bytes(), [0, 0]);
+    }
+
+    #[test]
+    fn detect() {
+        let mut driver = open(Simulator::<4>::new());
+        let mode = Mode {
+            word_length: WordLength::Bits16,
+            crc_type: CrcType::Ansi,
+            ..Mode::default()
+        };
+        driver.write_register(mode, &mut Discard).unwrap();
+
+        let sim = driver.into_inner().release();
+        let mut driver = Driver::new(Ads131m::detect_ads131m04(sim).unwrap());
+        assert_eq!(driver.read_register::<Mode>(&mut Discard), Ok(mode));
+    }
+}
@@ -1,4 +1,6 @@
 //! Traits for supporting SPI interfaces
+//!
+//! For DMA transfers, use a [`FrameCodec`](crate::interface::FrameCodec) to prepare and decode frames directly
 
 use embedded_hal::spi::FullDuplex;
 use nb;
@@ -157,4 +159,314 @@ impl<I: FullDuplex<u8>> WordTransfer<u8> for I {
     }
 }
 
-// TODO: DMA Interfaces
+/// A [`Transfer`] implementation for an embedded-hal 1.0 [`SpiDevice`](embedded_hal_1::spi::SpiDevice)
+///
+/// Chip select is managed by the device, so each transfer is performed as a single SPI transaction
+#[cfg(feature = "embedded-hal-1")]
+#[derive(Debug)]
+pub struct DeviceInterface<D> {
+    device: D,
+}
+
+#[cfg(feature = "embedded-hal-1")]
+impl<D> DeviceInterface<D>
+where
+    D: embedded_hal_1::spi::SpiDevice<u8>,
+{
+    /// Create a new interface using a SPI device
+    ///
+    /// The device must be configured for SPI mode 1
+    pub const fn new(device: D) -> Self {
+        Self { device }
+    }
+
+    /// Destroy the interface and return the SPI device
+    pub fn release(self) -> D {
+        self.device
+    }
+}
+
+#[cfg(feature = "embedded-hal-1")]
+impl<D> Transfer<u8> for DeviceInterface<D>
+where
+    D: embedded_hal_1::spi::SpiDevice<u8>,
+{
+    fn transfer(&mut self, send: &[u8], receive: &mut [u8]) -> Result<(), Error> {
+        use embedded_hal_1::spi::Operation;
+
+        let overlap = prepare_in_place(send, receive);
+        let result = if overlap < send.len() {
+            self.device.transaction(&mut [
+                Operation::TransferInPlace(receive),
+                Operation::Write(&send[overlap..]),
+            ])
+        } else {
+            self.device
+                .transaction(&mut [Operation::TransferInPlace(receive)])
+        };
+
+        result.map_err(|_| Error::SpiIOError)
+    }
+}
+
+/// A [`Transfer`] implementation for an embedded-hal 1.0 [`SpiBus`](embedded_hal_1::spi::SpiBus),
+/// with a dedicated chip select pin
+///
+/// The chip select pin is driven low for the duration of each transfer
+#[cfg(feature = "embedded-hal-1")]
+#[derive(Debug)]
+pub struct BusInterface<B, CS> {
+    bus: B,
+    cs: CS,
+}
+
+#[cfg(feature = "embedded-hal-1")]
+impl<B, CS> BusInterface<B, CS>
+where
+    B: embedded_hal_1::spi::SpiBus<u8>,
+    CS: embedded_hal_1::digital::OutputPin,
+{
+    /// Create a new interface using a SPI bus and a chip select pin
+    ///
+    /// The bus must be configured for SPI mode 1
+    ///
+    /// # Errors
+    ///
+    /// Will return `Err` if the chip select pin could not be driven high
+    pub fn new(bus: B, mut cs: CS) -> Result<Self, Error> {
+        cs.set_high().map_err(|_| Error::SpiIOError)?;
+        Ok(Self { bus, cs })
+    }
+
+    /// Destroy the interface and return the SPI bus and chip select pin
+    pub fn release(self) -> (B, CS) {
+        (self.bus, self.cs)
+    }
+
+    fn transfer_selected(&mut self, send: &[u8], receive: &mut [u8]) -> Result<(), B::Error> {
+        let overlap = prepare_in_place(send, receive);
+        self.bus.transfer_in_place(receive)?;
+        if overlap < send.len() {
+            self.bus.write(&send[overlap..])?;
+        }
+
+        self.bus.flush()
+    }
+}
+
+#[cfg(feature = "embedded-hal-1")]
+impl<B, CS> Transfer<u8> for BusInterface<B, CS>
+where
+    B: embedded_hal_1::spi::SpiBus<u8>,
+    CS: embedded_hal_1::digital::OutputPin,
+{
+    fn transfer(&mut self, send: &[u8], receive: &mut [u8]) -> Result<(), Error> {
+        self.cs.set_low().map_err(|_| Error::SpiIOError)?;
+        let result = self.transfer_selected(send, receive);
+        let deselect = self.cs.set_high();
+
+        result.map_err(|_| Error::SpiIOError)?;
+        deselect.map_err(|_| Error::SpiIOError)
+    }
+}
+
+/// Copy `send` into `receive` to be transferred in place, padding `receive` with zeros
+///
+/// Returns the number of bytes of `send` copied. Any remaining bytes must be written after `receive`
+#[cfg(feature = "embedded-hal-1")]
+pub(crate) fn prepare_in_place(send: &[u8], receive: &mut [u8]) -> usize {
+    let overlap = core::cmp::min(send.len(), receive.len());
+    receive[..overlap].copy_from_slice(&send[..overlap]);
+    receive[overlap..].fill(0);
+
+    overlap
+}
+
+#[cfg(test)]
+pub(crate) mod replay {
+    extern crate std;
+
+    use std::vec::Vec;
+
+    use crc::{Crc, CRC_16_IBM_3740};
+
+    use super::Transfer;
+    use crate::driver::Driver;
+    use crate::interface::Ads131m;
+    use crate::Error;
+
+    /// Build a 24-bit frame from 16-bit words, without the CRC
+    pub fn frame(words: &[[u8; 2]]) -> Vec<u8> {
+        words.iter().flat_map(|w| [w[0], w[1], 0]).collect()
+    }
+
+    /// Open an `ADS131M04` driver which replays 24-bit frames
+    pub fn open(frames: &[Vec<u8>]) -> Driver<ReplaySpi, u8, 4> {
+        let frames: Vec<&[u8]> = frames.iter().map(Vec::as_slice).collect();
+        Driver::new(Ads131m::open_ads131m04(ReplaySpi::new(3, &frames)))
+    }
+
+    /// A SPI interface which replays a list of device frames, and records the frames sent to it
+    pub struct ReplaySpi {
+        responses: Vec<Vec<u8>>,
+        pub sent: Vec<Vec<u8>>,
+    }
+
+    impl ReplaySpi {
+        /// Build a replay interface from device frames, appending a CCITT CRC word to each frame
+        pub fn new(word_len: usize, frames: &[&[u8]]) -> Self {
+            let crc = Crc::<u16>::new(&CRC_16_IBM_3740);
+            let responses = frames
+                .iter()
+                .rev()
+                .map(|frame| {
+                    let mut frame = frame.to_vec();
+                    frame.extend_from_slice(&crc.checksum(&frame).to_be_bytes());
+                    frame.resize(frame.len() + word_len - 2, 0);
+                    frame
+                })
+                .collect();
+
+            Self {
+                responses,
+                sent: Vec::new(),
+            }
+        }
+
+        /// Check if every frame has been replayed
+        pub fn is_done(&self) -> bool {
+            self.responses.is_empty()
+        }
+    }
+
+    impl Transfer<u8> for ReplaySpi {
+        fn transfer(&mut self, send: &[u8], receive: &mut [u8]) -> Result<(), Error> {
+            let mut frame = self.responses.pop().expect("unexpected transfer");
+            frame.resize(receive.len(), 0);
+            receive.copy_from_slice(&frame);
+            self.sent.push(send.to_vec());
+            Ok(())
+        }
+    }
+}
+
+#[cfg(all(test, feature = "embedded-hal-1"))]
+mod tests {
+    extern crate std;
+
+    use core::convert::Infallible;
+    use std::vec::Vec;
+
+    use embedded_hal_1::digital::{ErrorType as PinErrorType, OutputPin};
+    use embedded_hal_1::spi::{ErrorType, Operation, SpiBus, SpiDevice};
+
+    use super::*;
+
+    /// A SPI bus which records the bytes written to it, and responds with a counting sequence
+    #[derive(Default)]
+    struct RecordingBus {
+        written: Vec<u8>,
+        counter: u8,
+    }
+
+    impl ErrorType for RecordingBus {
+        type Error = Infallible;
+    }
+
+    impl SpiBus<u8> for RecordingBus {
+        fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+            SpiBus::transfer(self, words, &[])
+        }
+
+        fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+            SpiBus::transfer(self, &mut [], words)
+        }
+
+        fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+            for idx in 0..core::cmp::max(read.len(), write.len()) {
+                self.written.push(write.get(idx).copied().unwrap_or(0));
+                if let Some(byte) = read.get_mut(idx) {
+                    *byte = self.counter;
+                }
+                self.counter += 1;
+            }
+            Ok(())
+        }
+
+        fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+            let write = words.to_vec();
+            SpiBus::transfer(self, words, &write)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    impl SpiDevice<u8> for RecordingBus {
+        fn transaction(&mut self, operations: &mut [Operation<'_, u8>]) -> Result<(), Self::Error> {
+            for op in operations {
+                match op {
+                    Operation::Read(words) => SpiBus::read(self, words)?,
+                    Operation::Write(words) => SpiBus::write(self, words)?,
+                    Operation::Transfer(read, write) => SpiBus::transfer(self, read, write)?,
+                    Operation::TransferInPlace(words) => {
+                        SpiBus::transfer_in_place(self, words)?;
+                    }
+                    Operation::DelayNs(_) => {}
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// A pin which records each level it is driven to
+    #[derive(Default)]
+    struct RecordingPin {
+        levels: Vec<bool>,
+    }
+
+    impl PinErrorType for RecordingPin {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for RecordingPin {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.levels.push(false);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.levels.push(true);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn device_transfer() {
+        let mut intf = DeviceInterface::new(RecordingBus::default());
+
+        let mut receive = [0xAA; 6];
+        intf.transfer(&[1, 2, 3, 4], &mut receive).unwrap();
+        assert_eq!(receive, [0, 1, 2, 3, 4, 5]);
+
+        let mut receive = [0xAA; 2];
+        intf.transfer(&[1, 2, 3, 4], &mut receive).unwrap();
+        assert_eq!(receive, [6, 7]);
+
+        assert_eq!(intf.release().written, [1, 2, 3, 4, 0, 0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn bus_transfer() {
+        let mut intf = BusInterface::new(RecordingBus::default(), RecordingPin::default()).unwrap();
+
+        let mut receive = [0xAA; 4];
+        intf.transfer(&[1, 2, 3, 4, 5, 6], &mut receive).unwrap();
+        assert_eq!(receive, [0, 1, 2, 3]);
+
+        let (bus, cs) = intf.release();
+        assert_eq!(bus.written, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(cs.levels, [true, false, true]);
+    }
+}
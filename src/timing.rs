@@ -0,0 +1,180 @@
+//! Data rate and latency calculations from the `CLOCK` register settings
+//!
+//! The modulator runs at half the `CLKIN` frequency in every power mode,
+//! and each conversion takes [`Clock::modulator_cycles_per_conversion`] modulator cycles.
+//! The latency figures are for the sinc3 decimation filter, which takes three conversion periods to settle
+
+use crate::register::{Clock, GlobalChopDelay, OversamplingRatio};
+
+/// The nominal `CLKIN` frequency in high resolution mode, in hertz
+pub const NOMINAL_CLKIN_HZ: u32 = 8_192_000;
+
+/// The highest channel count supporting turbo mode
+pub(crate) const MAX_TURBO_CHANNELS: usize = 4;
+
+/// The number of conversion periods the decimation filter takes to settle
+const FILTER_SETTLING_CONVERSIONS: u32 = 3;
+
+/// Every oversampling ratio, in increasing order
+const OVERSAMPLING_RATIOS: [OversamplingRatio; 8] = [
+    OversamplingRatio::Osr128,
+    OversamplingRatio::Osr256,
+    OversamplingRatio::Osr512,
+    OversamplingRatio::Osr1024,
+    OversamplingRatio::Osr2048,
+    OversamplingRatio::Osr4096,
+    OversamplingRatio::Osr8192,
+    OversamplingRatio::Osr16256,
+];
+
+/// The modulator clock frequency for a `CLKIN` frequency of `clkin_hz`, in hertz
+#[must_use]
+pub fn modulator_frequency(clkin_hz: u32) -> f64 {
+    f64::from(clkin_hz) / 2.0
+}
+
+/// The output data rate, in samples per second
+#[must_use]
+pub fn data_rate(clkin_hz: u32, clock: &Clock) -> f64 {
+    modulator_frequency(clkin_hz) / f64::from(clock.modulator_cycles_per_conversion())
+}
+
+/// The group delay of the decimation filter, in seconds
+///
+/// This is the delay between a change at the input and the same change in the output data
+#[must_use]
+pub fn group_delay(clkin_hz: u32, clock: &Clock) -> f64 {
+    settling_time(clkin_hz, clock) / 2.0
+}
+
+/// The time for the decimation filter to fully settle after a step change at the input, in seconds
+#[must_use]
+pub fn settling_time(clkin_hz: u32, clock: &Clock) -> f64 {
+    f64::from(FILTER_SETTLING_CONVERSIONS) / data_rate(clkin_hz, clock)
+}
+
+/// The effective output data rate in global-chop mode, in samples per second
+///
+/// Each time the inputs are chopped the device waits for `delay`, then discards
+/// conversions until the decimation filter has settled, so only one conversion is output per settling time
+#[must_use]
+pub fn global_chop_data_rate(clkin_hz: u32, clock: &Clock, delay: GlobalChopDelay) -> f64 {
+    let cycles = FILTER_SETTLING_CONVERSIONS * u32::from(clock.modulator_cycles_per_conversion())
+        + delay.modulator_cycles();
+
+    modulator_frequency(clkin_hz) / f64::from(cycles)
+}
+
+/// The oversampling ratio and turbo mode setting giving the output data rate closest to `data_rate_hz`
+///
+/// Turbo mode is only considered for devices with `channel_count` of 4 or fewer,
+/// and always uses [`OversamplingRatio::Osr128`].
+/// Returns `None` if `data_rate_hz` is not a positive number
+#[must_use]
+pub fn closest_oversampling(
+    clkin_hz: u32,
+    data_rate_hz: f64,
+    channel_count: usize,
+) -> Option<(OversamplingRatio, bool)> {
+    if data_rate_hz.is_nan() || data_rate_hz <= 0.0 {
+        return None;
+    }
+
+    let turbo = Clock {
+        turbo_mode: true,
+        oversampling_ratio: OversamplingRatio::Osr128,
+        ..Clock::default()
+    };
+    let candidates = OVERSAMPLING_RATIOS
+        .iter()
+        .map(|&oversampling_ratio| Clock {
+            oversampling_ratio,
+            ..Clock::default()
+        })
+        .chain((channel_count <= MAX_TURBO_CHANNELS).then_some(turbo));
+
+    let mut closest = None;
+    let mut closest_error = f64::INFINITY;
+    for clock in candidates {
+        let error = data_rate(clkin_hz, &clock) - data_rate_hz;
+        let error = if error < 0.0 { -error } else { error };
+        if error < closest_error {
+            closest = Some((clock.oversampling_ratio, clock.turbo_mode));
+            closest_error = error;
+        }
+    }
+
+    closest
+}
+
+#[cfg(test)]
+mod tests {
+    use float_cmp::assert_approx_eq;
+
+    use super::*;
+
+    fn clock(oversampling_ratio: OversamplingRatio, turbo_mode: bool) -> Clock {
+        Clock {
+            turbo_mode,
+            oversampling_ratio,
+            ..Clock::default()
+        }
+    }
+
+    #[test]
+    fn rates() {
+        assert_approx_eq!(f64, modulator_frequency(NOMINAL_CLKIN_HZ), 4_096_000.0);
+        assert_approx_eq!(f64, data_rate(NOMINAL_CLKIN_HZ, &Clock::default()), 4_000.0);
+        assert_approx_eq!(
+            f64,
+            data_rate(NOMINAL_CLKIN_HZ, &clock(OversamplingRatio::Osr128, true)),
+            64_000.0
+        );
+        assert_approx_eq!(
+            f64,
+            data_rate(
+                NOMINAL_CLKIN_HZ / 4,
+                &clock(OversamplingRatio::Osr128, false)
+            ),
+            8_000.0
+        );
+    }
+
+    #[test]
+    fn latency() {
+        let clock = Clock::default();
+        assert_approx_eq!(f64, settling_time(NOMINAL_CLKIN_HZ, &clock), 750e-6);
+        assert_approx_eq!(f64, group_delay(NOMINAL_CLKIN_HZ, &clock), 375e-6);
+        assert_approx_eq!(
+            f64,
+            global_chop_data_rate(NOMINAL_CLKIN_HZ, &clock, GlobalChopDelay::Delay16),
+            4_096_000.0 / 3_088.0
+        );
+    }
+
+    #[test]
+    fn closest() {
+        assert_eq!(
+            closest_oversampling(NOMINAL_CLKIN_HZ, 4_000.0, 4),
+            Some((OversamplingRatio::Osr1024, false))
+        );
+        assert_eq!(
+            closest_oversampling(NOMINAL_CLKIN_HZ, 2_500.0, 4),
+            Some((OversamplingRatio::Osr2048, false))
+        );
+        assert_eq!(
+            closest_oversampling(NOMINAL_CLKIN_HZ, 60_000.0, 4),
+            Some((OversamplingRatio::Osr128, true))
+        );
+        assert_eq!(
+            closest_oversampling(NOMINAL_CLKIN_HZ, 60_000.0, 8),
+            Some((OversamplingRatio::Osr128, false))
+        );
+        assert_eq!(
+            closest_oversampling(NOMINAL_CLKIN_HZ, 1.0, 4),
+            Some((OversamplingRatio::Osr16256, false))
+        );
+        assert_eq!(closest_oversampling(NOMINAL_CLKIN_HZ, 0.0, 4), None);
+        assert_eq!(closest_oversampling(NOMINAL_CLKIN_HZ, f64::NAN, 4), None);
+    }
+}